@@ -0,0 +1,402 @@
+use crate::renderer::{render_code_block, render_option_list, OptionItem, OPTION_CONFIG_ORDER};
+
+/// Normalize the formatting of a maskfile while leaving prose sections untouched.
+///
+/// Headings, OPTIONS lists and fenced code blocks are rewritten in their canonical
+/// form and every block is separated by exactly one blank line.
+pub fn format(maskfile_contents: String) -> String {
+    let lines: Vec<&str> = maskfile_contents.lines().collect();
+    let mut blocks: Vec<String> = vec![];
+    // Lines of the prose block that is currently being collected
+    let mut prose: Vec<String> = vec![];
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if line.trim().is_empty() {
+            // Blank lines within indented content (nested lists, indented code) are kept
+            let next_non_blank = lines[i..].iter().find(|l| !l.trim().is_empty());
+            match next_non_blank {
+                Some(next) if !prose.is_empty() && indentation(next) >= 4 => {
+                    prose.push("".to_string())
+                }
+                _ => flush_prose(&mut blocks, &mut prose),
+            }
+            i += 1;
+            continue;
+        }
+
+        if let Some((fence, info)) = fence_start(line) {
+            let indent = indentation(line);
+            let end = find_fence_end(&lines, i + 1, &fence);
+            if indent == 0 {
+                flush_prose(&mut blocks, &mut prose);
+                let source: String = lines[i + 1..end]
+                    .iter()
+                    .map(|l| format!("{}\n", l))
+                    .collect();
+                blocks.push(render_code_block(info.trim(), &source));
+            } else {
+                // Fences nested within other blocks like lists are kept as is
+                let last = std::cmp::min(end, lines.len() - 1);
+                prose.extend(lines[i..=last].iter().map(|l| l.to_string()));
+            }
+            i = end + 1;
+            continue;
+        }
+
+        if let Some(heading) = atx_heading(line) {
+            flush_prose(&mut blocks, &mut prose);
+            blocks.push(heading);
+            i += 1;
+            continue;
+        }
+
+        if let Some(level) = setext_underline(line) {
+            if prose.len() == 1 && !starts_block_construct(&prose[0]) {
+                let text = prose.pop().unwrap();
+                blocks.push(format!("{} {}", "#".repeat(level), normalize_spaces(&text)));
+                i += 1;
+                continue;
+            }
+        }
+
+        if is_options_marker(line) {
+            if let Some((items, end)) = parse_option_list(&lines, i + 1) {
+                flush_prose(&mut blocks, &mut prose);
+                blocks.push(render_option_list(&items));
+                i = end;
+                continue;
+            }
+        }
+
+        prose.push(line.to_string());
+        i += 1;
+    }
+    flush_prose(&mut blocks, &mut prose);
+
+    let mut formatted = blocks.join("\n\n");
+    formatted.push('\n');
+    formatted
+}
+
+fn flush_prose(blocks: &mut Vec<String>, prose: &mut Vec<String>) {
+    if !prose.is_empty() {
+        blocks.push(prose.join("\n"));
+        prose.clear();
+    }
+}
+
+fn indentation(line: &str) -> usize {
+    line.chars().take_while(|c| *c == ' ').count()
+}
+
+/// Returns the fence and info string if this line opens a fenced code block
+fn fence_start(line: &str) -> Option<(String, String)> {
+    let trimmed = line.trim_start();
+    let fence_char = trimmed.chars().next()?;
+    if fence_char != '`' && fence_char != '~' {
+        return None;
+    }
+
+    let fence: String = trimmed.chars().take_while(|c| *c == fence_char).collect();
+    let info = &trimmed[fence.len()..];
+    // Backtick fences can't contain backticks in their info string
+    if fence.len() < 3 || (fence_char == '`' && info.contains('`')) {
+        return None;
+    }
+
+    Some((fence, info.to_string()))
+}
+
+/// Returns the index of the closing fence line, or the index past the last line if unclosed
+fn find_fence_end(lines: &[&str], start: usize, fence: &str) -> usize {
+    let fence_char = fence.chars().next().unwrap();
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let trimmed = line.trim();
+        if trimmed.len() >= fence.len() && trimmed.chars().all(|c| c == fence_char) {
+            return i;
+        }
+    }
+    lines.len()
+}
+
+fn atx_heading(line: &str) -> Option<String> {
+    if indentation(line) > 3 {
+        return None;
+    }
+
+    let trimmed = line.trim();
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    let rest = &trimmed[level..];
+    if level == 0 || level > 6 || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    // Remove the optional closing sequence of #'s
+    let mut text = rest.trim();
+    let without_closing = text.trim_end_matches('#');
+    if without_closing.is_empty() || without_closing.ends_with([' ', '\t']) {
+        text = without_closing.trim();
+    }
+
+    Some(format!("{} {}", "#".repeat(level), normalize_spaces(text)))
+}
+
+fn setext_underline(line: &str) -> Option<usize> {
+    if indentation(line) > 3 {
+        return None;
+    }
+
+    let trimmed = line.trim();
+    if !trimmed.is_empty() && trimmed.chars().all(|c| c == '=') {
+        Some(1)
+    } else if !trimmed.is_empty() && trimmed.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Lines that can't be the content of a setext heading
+fn starts_block_construct(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    indentation(line) > 3
+        || trimmed.starts_with(['>', '*', '-', '+', '<', '|'])
+        || trimmed.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn normalize_spaces(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn is_options_marker(line: &str) -> bool {
+    matches!(line.trim(), "**OPTIONS**" | "__OPTIONS__" | "OPTIONS")
+}
+
+/// Parses the OPTIONS list that starts at `start`. Returns the index of the line after it.
+///
+/// If the list contains anything that isn't understood, None is returned so that it's
+/// left untouched.
+fn parse_option_list(lines: &[&str], start: usize) -> Option<(Vec<OptionItem>, usize)> {
+    let mut items: Vec<OptionItem> = vec![];
+    let mut i = start;
+
+    // Allow blank lines between the OPTIONS marker and its list
+    while i < lines.len() && lines[i].trim().is_empty() {
+        i += 1;
+    }
+
+    while i < lines.len() {
+        let line = lines[i];
+        // The list ends at a blank line or a block that interrupts it
+        if line.trim().is_empty()
+            || (indentation(line) < 2
+                && (fence_start(line).is_some() || atx_heading(line).is_some()))
+        {
+            break;
+        }
+
+        let item_text = list_item_text(line)?;
+        if indentation(line) < 2 {
+            items.push(OptionItem {
+                name: item_text.to_string(),
+                config: vec![],
+            });
+        } else {
+            let item = items.last_mut()?;
+            let mut config_split = item_text.splitn(2, ':');
+            let key = config_split.next().unwrap_or("").trim().to_string();
+            let val = config_split.next().unwrap_or("").trim();
+            let val = match key.as_str() {
+                "flags" => normalize_flags(val),
+                "choices" => val
+                    .split(',')
+                    .map(|choice| choice.trim())
+                    .collect::<Vec<&str>>()
+                    .join(", "),
+                _ => val.to_string(),
+            };
+            item.config.push((key, val));
+        }
+        i += 1;
+    }
+
+    if items.is_empty() {
+        return None;
+    }
+
+    for item in &mut items {
+        // Unknown keys keep their relative order at the end
+        item.config.sort_by_key(|(key, _)| {
+            OPTION_CONFIG_ORDER
+                .iter()
+                .position(|k| k == key)
+                .unwrap_or(OPTION_CONFIG_ORDER.len())
+        });
+    }
+
+    Some((items, i))
+}
+
+fn list_item_text(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    let marker = trimmed.chars().next()?;
+    if !matches!(marker, '*' | '-' | '+') {
+        return None;
+    }
+    let text = &trimmed[1..];
+    if !text.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some(text.trim())
+}
+
+/// Puts the short flag before the long flag
+fn normalize_flags(val: &str) -> String {
+    let (long, short): (Vec<&str>, Vec<&str>) =
+        val.split_whitespace().partition(|f| f.starts_with("--"));
+    short
+        .into_iter()
+        .chain(long)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod format {
+    use super::*;
+
+    #[test]
+    fn normalizes_the_maskfile_formatting() {
+        let unformatted = r#"
+
+Tasks
+=====
+
+
+Some **prose** that
+spans lines.
+## serve   (port) ##
+> Serve the app
+**OPTIONS**
+* env
+    * desc: Which environment to use
+    * choices: dev,prod
+    * type: string
+    * flags: --env  -e
+~~~bash
+echo "Serving on port $port"
+~~~
+
+
+
+## parent
+### parent subcommand
+Subcommand notes:
+* one
+* two
+
+        indented code
+
+        stays as is
+```js
+console.log("hey");
+```
+"#;
+
+        let expected = r#"# Tasks
+
+Some **prose** that
+spans lines.
+
+## serve (port)
+
+> Serve the app
+
+**OPTIONS**
+* env
+    * flags: -e --env
+    * type: string
+    * desc: Which environment to use
+    * choices: dev, prod
+
+```bash
+echo "Serving on port $port"
+```
+
+## parent
+
+### parent subcommand
+
+Subcommand notes:
+* one
+* two
+
+        indented code
+
+        stays as is
+
+```js
+console.log("hey");
+```
+"#;
+
+        assert_eq!(expected, format(unformatted.to_string()));
+    }
+
+    #[test]
+    fn is_idempotent() {
+        let formatted = crate::parse(TEST_MASKFILE.to_string()).to_markdown();
+
+        assert_eq!(formatted, format(formatted.clone()));
+        assert_eq!(
+            format(TEST_MASKFILE.to_string()),
+            format(format(TEST_MASKFILE.to_string()))
+        );
+    }
+
+    #[test]
+    fn keeps_the_parsed_structure() {
+        let formatted = format(TEST_MASKFILE.to_string());
+
+        assert_eq!(
+            crate::parse(TEST_MASKFILE.to_string()).without_spans(),
+            crate::parse(formatted).without_spans()
+        );
+    }
+
+    #[test]
+    fn leaves_code_block_contents_untouched() {
+        let unformatted = "## test\n\n~~~sh\n## not a heading\n\n\n\n**OPTIONS**\n* nope\n~~~\n";
+
+        assert_eq!(
+            "## test\n\n```sh\n## not a heading\n\n\n\n**OPTIONS**\n* nope\n```\n",
+            format(unformatted.to_string())
+        );
+    }
+
+    #[test]
+    fn leaves_unknown_option_lists_untouched() {
+        let unformatted = "**OPTIONS**\n* port\n    * flags: -p\n      continued\n";
+
+        assert_eq!(unformatted, format(unformatted.to_string()));
+    }
+
+    const TEST_MASKFILE: &str = r#"
+# Document Title
+## serve (port)
+> Serve the app on the `port`
+
+**OPTIONS**
+* port
+    * type: number
+    * flags: --port
+* verbose_logs
+    * flags: -l
+~~~bash
+echo "Serving on port $port"
+~~~
+"#;
+}
@@ -1,4 +1,11 @@
+// The json! macro in the parser tests nests deeper than the default limit
+#![cfg_attr(test, recursion_limit = "256")]
+
+mod formatter;
 pub mod maskfile;
 mod parser;
+mod renderer;
+mod validator;
 
-pub use parser::parse;
+pub use formatter::format;
+pub use parser::{parse, parse_with_options, ParseOptions};
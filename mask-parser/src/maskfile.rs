@@ -1,25 +1,165 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Maskfile {
     pub title: String,
     pub description: String,
     pub commands: Vec<Command>,
+    /// Problems found while parsing. Only serialized when there are some.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl Maskfile {
     pub fn to_json(&self) -> Result<Value, serde_json::Error> {
-        serde_json::to_value(&self)
+        serde_json::to_value(self)
+    }
+
+    /// Render the maskfile back into canonical maskfile markdown.
+    pub fn to_markdown(&self) -> String {
+        crate::renderer::render(self)
+    }
+
+    /// Clears all source locations so maskfiles parsed from different sources can be compared.
+    #[cfg(test)]
+    pub(crate) fn without_spans(mut self) -> Self {
+        fn clear_spans(cmd: &mut Command) {
+            cmd.span = Span::default();
+            cmd.scripts
+                .iter_mut()
+                .for_each(|s| s.span = Span::default());
+            cmd.examples
+                .iter_mut()
+                .for_each(|e| e.span = Span::default());
+            cmd.required_args
+                .iter_mut()
+                .for_each(|a| a.span = Span::default());
+            cmd.optional_args
+                .iter_mut()
+                .for_each(|a| a.span = Span::default());
+            cmd.named_flags
+                .iter_mut()
+                .for_each(|f| f.span = Span::default());
+            cmd.preconditions
+                .iter_mut()
+                .for_each(|p| p.span = Span::default());
+            cmd.subcommands.iter_mut().for_each(clear_spans);
+        }
+        self.commands.iter_mut().for_each(clear_spans);
+        self.diagnostics
+            .iter_mut()
+            .for_each(|d| d.span = Span::default());
+        self
+    }
+}
+
+/// The location of something within the maskfile source.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    /// Byte offset where it starts
+    pub start: usize,
+    /// Byte offset where it ends (exclusive)
+    pub end: usize,
+    /// Line number where it starts (1-based). 0 means it has no location in the source.
+    pub line: usize,
+}
+
+/// A problem with the maskfile that was found while parsing it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(span: Span, message: String) -> Self {
+        Self {
+            span,
+            severity: Severity::Error,
+            message,
+        }
+    }
+
+    pub fn warning(span: Span, message: String) -> Self {
+        Self {
+            span,
+            severity: Severity::Warning,
+            message,
+        }
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// mask will fail to run the command
+    Error,
+    /// Likely a mistake, though mask can still run
+    Warning,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Command {
+    pub span: Span,
     pub level: u8,
     pub name: String,
     pub description: String,
-    pub script: Option<Script>,
+    /// Other names the command can be run with
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Hidden commands can still be run but aren't shown in the help output
+    #[serde(default)]
+    pub hidden: bool,
+    /// The default command runs when none of its siblings are given
+    #[serde(default)]
+    pub default: bool,
+    /// Prompt for missing required args and flags instead of failing when run in a terminal
+    #[serde(default)]
+    pub prompt: bool,
+    /// Asks the user to confirm with this message before running. Empty uses a default message.
+    #[serde(default)]
+    pub confirm: Option<String>,
+    /// How long the command can run for, like `10m` or `1h 30m`
+    #[serde(default)]
+    pub timeout: Option<String>,
+    /// How many more times to run the command when it fails
+    #[serde(default)]
+    pub retries: u32,
+    /// How long to wait before retrying, like `5s`
+    #[serde(default)]
+    pub retry_delay: Option<String>,
+    /// Double the retry delay after every attempt
+    #[serde(default)]
+    pub retry_backoff: bool,
+    /// The command's code blocks run before any of its subcommands instead of on their own, and
+    /// its `finally` blocks after them
+    #[serde(default)]
+    pub setup: bool,
+    /// Programs that have to be in PATH to run the command, optionally with a version like `node>=18`
+    #[serde(default)]
+    pub requires: Vec<String>,
+    /// Checks that have to pass before the command runs
+    #[serde(default)]
+    pub preconditions: Vec<Precondition>,
+    /// Globs of the files the command uses, relative to the maskfile. It's skipped when they
+    /// haven't changed since it last ran, or since its outputs were made.
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Globs of the files the command makes, relative to the maskfile
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    /// Globs of the files that make the command run again with `--watch`, relative to the maskfile
+    #[serde(default)]
+    pub watch: Vec<String>,
+    /// Every code block of the command in order, including the ones for other operating systems
+    #[serde(default)]
+    pub scripts: Vec<Script>,
+    /// Code blocks that are only documentation and never run, like a `json` or `bash norun` block
+    #[serde(default)]
+    pub examples: Vec<Example>,
     pub subcommands: Vec<Command>,
     pub required_args: Vec<RequiredArg>,
     pub optional_args: Vec<OptionalArg>,
@@ -29,10 +169,27 @@ pub struct Command {
 impl Command {
     pub fn new(level: u8) -> Self {
         Self {
+            span: Span::default(),
             level,
             name: "".to_string(),
             description: "".to_string(),
-            script: Some(Script::new()),
+            aliases: vec![],
+            hidden: false,
+            default: false,
+            prompt: false,
+            confirm: None,
+            timeout: None,
+            retries: 0,
+            retry_delay: None,
+            retry_backoff: false,
+            setup: false,
+            requires: vec![],
+            preconditions: vec![],
+            sources: vec![],
+            outputs: vec![],
+            watch: vec![],
+            scripts: vec![],
+            examples: vec![],
             subcommands: vec![],
             required_args: vec![],
             optional_args: vec![],
@@ -41,51 +198,168 @@ impl Command {
     }
 
     pub fn build(mut self) -> Self {
-        // Set to None if there is no source and executor
-        if let Some(s) = &mut self.script {
-            if s.source.is_empty() && s.executor.is_empty() {
-                self.script = None;
+        // Auto add common flags like verbose for commands that have a script source
+        if !self.scripts.is_empty() {
+            self.named_flags.push(NamedFlag::verbose());
+        }
+        self
+    }
+
+    /// Checks if the command can be run with the given name, either its own or an alias
+    pub fn has_name(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|a| a == name)
+    }
+
+    /// Selects the scripts to run on the given OS (a value of `std::env::consts::OS`), in order.
+    /// Scripts made for that OS are used instead of the ones without an OS when there are any.
+    pub fn scripts_for(&self, os: &str) -> Vec<&Script> {
+        let for_os: Vec<&Script> = self
+            .scripts
+            .iter()
+            .filter(|s| s.os.as_deref() == Some(os))
+            .collect();
+        if !for_os.is_empty() {
+            return for_os;
+        }
+        self.scripts.iter().filter(|s| s.os.is_none()).collect()
+    }
+}
+
+/// A program a command requires, parsed from a `requires` entry like `node>=18`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Requirement<'a> {
+    pub program: &'a str,
+    /// The comparison and the version the program's version has to match, like `>=` and `18`
+    pub version: Option<(&'a str, &'a str)>,
+}
+
+impl<'a> Requirement<'a> {
+    /// Parses a program name followed by an optional `>=`, `>`, `<=`, `<` or `=` and version
+    pub fn parse(requirement: &'a str) -> Option<Self> {
+        let requirement = requirement.trim();
+        let (program, version) = match requirement.find(['<', '>', '=']) {
+            Some(idx) => {
+                let rest = &requirement[idx..];
+                let len = if rest.starts_with(">=") || rest.starts_with("<=") {
+                    2
+                } else {
+                    1
+                };
+                let (comparison, version) = rest.split_at(len);
+                let version = version.trim();
+                let is_version = version
+                    .split('.')
+                    .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()));
+                if !is_version {
+                    return None;
+                }
+                (requirement[..idx].trim(), Some((comparison, version)))
             }
+            None => (requirement, None),
+        };
+
+        if program.is_empty() || program.contains(char::is_whitespace) {
+            return None;
         }
+        Some(Self { program, version })
+    }
+}
 
-        // Auto add common flags like verbose for commands that have a script source
-        if self.script.is_some() {
-            self.named_flags.push(NamedFlag {
-                name: "verbose".to_string(),
-                description: "Sets the level of verbosity".to_string(),
-                short: "v".to_string(),
-                long: "verbose".to_string(),
-                multiple: false,
-                takes_value: false,
-                required: false,
-                validate_as_number: false,
-                choices: vec![],
-                val: "".to_string(),
-            });
+/// A shell expression that has to succeed before the command runs
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Precondition {
+    pub span: Span,
+    pub check: String,
+    /// Shown when the check fails instead of a message naming the check
+    pub message: Option<String>,
+}
+
+impl Precondition {
+    /// Parses a preconditions item, which is a check in backticks followed by an optional message
+    /// like `` `test -f .env`: Copy .env.example to .env first ``. Without backticks the whole
+    /// item is the check.
+    pub fn parse(item: &str, span: Span) -> Self {
+        let item = item.trim();
+        let quoted = item.strip_prefix('`').and_then(|rest| rest.split_once('`'));
+        let (check, message) = match quoted {
+            Some((check, rest)) => {
+                let message = rest.trim().trim_start_matches(':').trim();
+                (check, Some(message).filter(|m| !m.is_empty()))
+            }
+            None => (item, None),
+        };
+
+        Self {
+            span,
+            check: check.to_string(),
+            message: message.map(str::to_string),
         }
-        self
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Script {
+    pub span: Span,
     // The executor to run the source with
     pub executor: String, // shell, node, ruby, python, etc...
     // The script source to execute
     pub source: String,
+    /// The OS this script is for (linux, macos, windows, etc...). None means it runs on any OS.
+    pub os: Option<String>,
+    /// Settings from the code block's info string like `{cwd="web", env="NODE_ENV=production"}`
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    /// Finally scripts run after the others even when they fail, like `sh finally`
+    #[serde(default)]
+    pub finally: bool,
 }
 
 impl Script {
     pub fn new() -> Self {
         Self {
+            span: Span::default(),
             executor: "".to_string(),
             source: "".to_string(),
+            os: None,
+            attributes: BTreeMap::new(),
+            finally: false,
         }
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+impl Default for Script {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Example {
+    pub span: Span,
+    // The lang code of the code block (json, yaml, text, etc...)
+    pub lang: String,
+    pub source: String,
+}
+
+impl Example {
+    pub fn new() -> Self {
+        Self {
+            span: Span::default(),
+            lang: "".to_string(),
+            source: "".to_string(),
+        }
+    }
+}
+
+impl Default for Example {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct RequiredArg {
+    pub span: Span,
     pub name: String,
     /// Used within mask. TODO: store in a different place within mask instead of here.
     #[serde(skip)]
@@ -95,14 +369,16 @@ pub struct RequiredArg {
 impl RequiredArg {
     pub fn new(name: String) -> Self {
         Self {
+            span: Span::default(),
             name,
             val: "".to_string(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct OptionalArg {
+    pub span: Span,
     pub name: String,
     /// Used within mask. TODO: store in a different place within mask instead of here.
     #[serde(skip)]
@@ -112,14 +388,16 @@ pub struct OptionalArg {
 impl OptionalArg {
     pub fn new(name: String) -> Self {
         Self {
+            span: Span::default(),
             name,
             val: "".to_string(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct NamedFlag {
+    pub span: Span,
     pub name: String,
     pub description: String,
     pub short: String,            // v        (used as -v)
@@ -129,6 +407,9 @@ pub struct NamedFlag {
     pub validate_as_number: bool, // Should we validate it as a number?
     pub choices: Vec<String>,     // Choices of flag value.
     pub required: bool,
+    /// Secret values aren't shown while they're typed into a prompt
+    #[serde(default)]
+    pub secret: bool,
     /// Used within mask. TODO: store in a different place within mask instead of here.
     #[serde(skip)]
     pub val: String,
@@ -137,6 +418,7 @@ pub struct NamedFlag {
 impl NamedFlag {
     pub fn new() -> Self {
         Self {
+            span: Span::default(),
             name: "".to_string(),
             description: "".to_string(),
             short: "".to_string(),
@@ -144,9 +426,27 @@ impl NamedFlag {
             multiple: false,
             takes_value: false,
             required: false,
+            secret: false,
             validate_as_number: false,
             choices: vec![],
             val: "".to_string(),
         }
     }
+
+    /// The flag that's automatically added to every command with a script.
+    pub(crate) fn verbose() -> Self {
+        Self {
+            name: "verbose".to_string(),
+            description: "Sets the level of verbosity".to_string(),
+            short: "v".to_string(),
+            long: "verbose".to_string(),
+            ..Self::new()
+        }
+    }
+}
+
+impl Default for NamedFlag {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -1,88 +1,238 @@
 use crate::maskfile::*;
-use pulldown_cmark::Event::{Code, End, InlineHtml, Start, Text};
-use pulldown_cmark::{Options, Parser, Tag};
+use crate::renderer::{OPTION_CONFIG_ORDER, SETTINGS_ORDER};
+use crate::validator::validate;
+use pulldown_cmark::Event::{Code, End, HardBreak, InlineHtml, SoftBreak, Start, Text};
+use pulldown_cmark::{CodeBlockKind, Options, Parser, Tag, TagEnd};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::time::Duration;
 
+/// Options for parsing a maskfile
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseOptions {
+    /// The OS the commands have to be runnable on to be included, using the values of
+    /// `std::env::consts::OS`. `None` includes the commands for every OS.
+    pub target_os: Option<String>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            target_os: Some(std::env::consts::OS.to_string()),
+        }
+    }
+}
+
+/// Parses the maskfile, leaving out the commands that can't run on the current OS
 pub fn parse(maskfile_contents: String) -> Maskfile {
+    parse_with_options(maskfile_contents, &ParseOptions::default())
+}
+
+/// Parses the maskfile for any OS. Commands keep the scripts for every OS and pick theirs with
+/// `scripts_for`.
+pub fn parse_with_options(maskfile_contents: String, options: &ParseOptions) -> Maskfile {
     let parser = create_markdown_parser(&maskfile_contents);
+    let line_index = LineIndex::new(&maskfile_contents);
     let mut commands = vec![];
     let mut current_command = Command::new(1);
     let mut current_option_flag = NamedFlag::new();
     let mut text = "".to_string();
     let mut list_level = 0;
+    let mut heading_range = 0..0;
+    let mut option_config_range = 0..0;
+    let mut settings_level = 0;
+    let mut setting_range = 0..0;
+    // The text of a setting with a nested list, like `preconditions`
+    let mut nested_setting = None;
+    let mut nested_setting_range = 0..0;
+    let mut diagnostics = vec![];
+    // How deep we are within lists and blockquotes. Code blocks nested in them are documentation.
+    let mut nesting_level = 0;
+    let mut blockquote_level = 0;
+    let mut blockquote_text = "".to_string();
+    let mut in_code_block = false;
+    let mut in_script_block = false;
+    let mut in_example_block = false;
 
-    for event in parser {
+    for (event, range) in parser.into_offset_iter() {
         match event {
             Start(tag) => {
-                match tag {
-                    Tag::Header(heading_level) => {
+                match &tag {
+                    Tag::Heading { level, .. } => {
+                        let heading_level = *level as u8;
                         // Add the last command before starting a new one.
                         // Don't add commands for level 1 heading blocks (the title).
                         if heading_level > 1 {
                             commands.push(current_command.build());
-                        } else if heading_level == 1 && commands.len() > 0 {
+                        } else if heading_level == 1 && !commands.is_empty() {
                             // Found another level 1 heading block, so quit parsing.
                             break;
                         }
-                        current_command = Command::new(heading_level as u8);
+                        current_command = Command::new(heading_level);
+                        current_command.span = line_index.span(&range);
+                        heading_range = range;
                     }
-                    #[cfg(not(windows))]
-                    Tag::CodeBlock(lang_code) => {
-                        if lang_code.to_string() != "powershell"
-                            && lang_code.to_string() != "batch"
-                            && lang_code.to_string() != "cmd"
-                        {
-                            if let Some(s) = &mut current_command.script {
-                                s.executor = lang_code.to_string();
-                            }
+                    Tag::CodeBlock(CodeBlockKind::Fenced(lang_code)) if nesting_level == 0 => {
+                        in_code_block = true;
+                        let info = CodeBlockInfo::parse(lang_code);
+                        if info.is_example() {
+                            in_example_block = true;
+                            current_command.examples.push(Example {
+                                span: line_index.span(&range),
+                                lang: info.lang,
+                                source: "".to_string(),
+                            });
+                        } else {
+                            in_script_block = true;
+                            current_command.scripts.push(Script {
+                                span: line_index.span(&range),
+                                executor: info.lang,
+                                source: "".to_string(),
+                                os: info.os,
+                                attributes: info.attributes,
+                                finally: info.finally,
+                            });
                         }
                     }
-                    #[cfg(windows)]
-                    Tag::CodeBlock(lang_code) => {
-                        if let Some(s) = &mut current_command.script {
-                            s.executor = lang_code.to_string();
+                    Tag::CodeBlock(kind) => {
+                        in_code_block = true;
+                        if let CodeBlockKind::Fenced(_) = kind {
+                            diagnostics.push(Diagnostic::warning(
+                                line_index.span(&range),
+                                "code blocks nested in a list or blockquote are not used as the command's script".to_string(),
+                            ));
                         }
                     }
-                    Tag::List(_) => {
-                        // We're in an options list if the current text above it is "OPTIONS"
-                        if text == "OPTIONS" || list_level > 0 {
-                            list_level += 1;
+                    Tag::BlockQuote(_) => {
+                        if blockquote_level == 0 {
+                            blockquote_text = "".to_string();
                         }
+                        blockquote_level += 1;
+                        nesting_level += 1;
+                    }
+                    // We're in an options list if the current text above it is "OPTIONS"
+                    // We're in a settings list if the current text above it is "SETTINGS"
+                    Tag::List(_) if text == "SETTINGS" || settings_level > 0 => {
+                        if settings_level == 1 {
+                            nested_setting = Some(text.clone());
+                        }
+                        settings_level += 1;
+                        nesting_level += 1;
+                    }
+                    Tag::Item if settings_level == 1 => {
+                        setting_range = range;
+                    }
+                    Tag::Item if settings_level == 2 => {
+                        nested_setting_range = range;
+                    }
+                    Tag::List(_) if text == "OPTIONS" || list_level > 0 => {
+                        list_level += 1;
+                        nesting_level += 1;
+                    }
+                    Tag::List(_) => {
+                        nesting_level += 1;
+                    }
+                    // Options level 1 items are the flags
+                    Tag::Item if list_level == 1 => {
+                        current_option_flag.span = line_index.span(&range);
+                    }
+                    Tag::Item if list_level == 2 => {
+                        option_config_range = range;
                     }
                     _ => (),
                 };
 
-                // Reset all state
-                text = "".to_string();
+                // Reset all state, unless it's inline formatting within the current text
+                if !is_inline(&tag) {
+                    text = "".to_string();
+                }
             }
             End(tag) => match tag {
-                Tag::Header(_) => {
-                    let (name, required_args, optional_args) =
+                TagEnd::Heading(_) => {
+                    let (name, mut required_args, mut optional_args) =
                         parse_command_name_required_and_optional_args(text.clone());
+
+                    // Locate each arg within the heading source
+                    let heading_source = &maskfile_contents[heading_range.clone()];
+                    let mut cursor = heading_source.find(['(', '[']).unwrap_or(0);
+                    let arg_spans = required_args
+                        .iter_mut()
+                        .map(|a| (&a.name, &mut a.span))
+                        .chain(optional_args.iter_mut().map(|a| (&a.name, &mut a.span)));
+                    for (arg_name, span) in arg_spans {
+                        if let Some(idx) = heading_source[cursor..].find(arg_name.as_str()) {
+                            let start = heading_range.start + cursor + idx;
+                            *span = line_index.span(&(start..start + arg_name.len()));
+                            cursor += idx + arg_name.len();
+                        }
+                    }
+
                     current_command.name = name;
                     current_command.required_args = required_args;
                     current_command.optional_args = optional_args;
                 }
-                Tag::BlockQuote => {
-                    current_command.description = text.clone();
+                // Every paragraph of a blockquote (including nested ones) is part of the description
+                TagEnd::Paragraph if blockquote_level > 0 => {
+                    if !blockquote_text.is_empty() {
+                        blockquote_text += " ";
+                    }
+                    blockquote_text += text.trim();
                 }
-                #[cfg(not(windows))]
-                Tag::CodeBlock(lang_code) => {
-                    if lang_code.to_string() != "powershell"
-                        && lang_code.to_string() != "batch"
-                        && lang_code.to_string() != "cmd"
-                    {
-                        if let Some(s) = &mut current_command.script {
+                TagEnd::BlockQuote(_) => {
+                    blockquote_level -= 1;
+                    nesting_level -= 1;
+                    if blockquote_level == 0 {
+                        current_command.description = blockquote_text.clone();
+                    }
+                }
+                TagEnd::CodeBlock => {
+                    if in_script_block {
+                        if let Some(s) = current_command.scripts.last_mut() {
                             s.source = text.to_string();
                         }
                     }
+                    if in_example_block {
+                        if let Some(e) = current_command.examples.last_mut() {
+                            e.source = text.to_string();
+                        }
+                    }
+                    in_code_block = false;
+                    in_script_block = false;
+                    in_example_block = false;
+                }
+                TagEnd::Item if settings_level == 1 => {
+                    let span = line_index.span(&setting_range);
+                    let setting = nested_setting.take().unwrap_or(text.clone());
+                    if let Some(diagnostic) = apply_setting(&mut current_command, &setting, span) {
+                        diagnostics.push(diagnostic);
+                    }
+                }
+                TagEnd::Item if settings_level == 2 => {
+                    let span = line_index.span(&nested_setting_range);
+                    let key = nested_setting.as_deref().unwrap_or("").split(':').next();
+                    if key.map(str::trim) == Some("preconditions") {
+                        let precondition = Precondition::parse(&text, span);
+                        current_command.preconditions.push(precondition);
+                    } else {
+                        diagnostics.push(Diagnostic::warning(
+                            span,
+                            "only the `preconditions` setting has a nested list".to_string(),
+                        ));
+                    }
                 }
-                #[cfg(windows)]
-                Tag::CodeBlock(_) => {
-                    if let Some(s) = &mut current_command.script {
-                        s.source = text.to_string();
+                TagEnd::List(_) if settings_level > 0 => {
+                    settings_level -= 1;
+                    nesting_level -= 1;
+                }
+                TagEnd::Item if list_level == 2 => {
+                    let span = line_index.span(&option_config_range);
+                    if let Some(diagnostic) = check_option_config(&text, span) {
+                        diagnostics.push(diagnostic);
                     }
                 }
-                Tag::List(_) => {
+                TagEnd::List(_) => {
+                    nesting_level -= 1;
+
                     // Don't go lower than zero (for cases where it's a non-OPTIONS list)
                     list_level = std::cmp::max(list_level - 1, 0);
 
@@ -97,8 +247,11 @@ pub fn parse(maskfile_contents: String) -> Maskfile {
                 }
                 _ => (),
             },
+            Text(body) if in_code_block => {
+                text += body.as_ref();
+            }
             Text(body) => {
-                text += &body.to_string();
+                text += body.as_ref();
 
                 // Options level 1 is the flag name
                 if list_level == 1 {
@@ -146,16 +299,22 @@ pub fn parse(maskfile_contents: String) -> Maskfile {
                         "required" => {
                             current_option_flag.required = true;
                         }
+                        "secret" => {
+                            current_option_flag.secret = true;
+                        }
                         _ => (),
                     };
                 }
             }
             InlineHtml(html) => {
-                text += &html.to_string();
+                text += html.as_ref();
             }
             Code(inline_code) => {
                 text += &format!("`{}`", inline_code);
             }
+            SoftBreak | HardBreak => {
+                text += " ";
+            }
             _ => (),
         };
     }
@@ -164,32 +323,359 @@ pub fn parse(maskfile_contents: String) -> Maskfile {
     commands.push(current_command.build());
 
     // Convert the flat commands array and to a tree of subcommands based on level
-    let all = treeify_commands(commands);
+    let all = treeify_commands(commands, options.target_os.as_deref());
     let root_command = all.first().expect("root command must exist");
 
-    Maskfile {
+    let mut maskfile = Maskfile {
         title: root_command.name.clone(),
         description: root_command.description.clone(),
         commands: root_command.subcommands.clone(),
+        diagnostics,
+    };
+    maskfile.diagnostics.extend(validate(&maskfile));
+    maskfile
+}
+
+fn check_option_config(config: &str, span: Span) -> Option<Diagnostic> {
+    let mut config_split = config.splitn(2, ':');
+    let param = config_split.next().unwrap_or("").trim();
+    let val = config_split.next().unwrap_or("").trim();
+
+    if !OPTION_CONFIG_ORDER.contains(&param) {
+        return Some(Diagnostic::warning(
+            span,
+            format!(
+                "unknown option `{}`, expected one of: {}",
+                param,
+                OPTION_CONFIG_ORDER.join(", ")
+            ),
+        ));
     }
+
+    if param == "type" && !["string", "number", "boolean"].contains(&val) {
+        return Some(Diagnostic::warning(
+            span,
+            format!(
+                "unknown type `{}`, expected one of: string, number, boolean",
+                val
+            ),
+        ));
+    }
+
+    None
 }
 
-fn create_markdown_parser<'a>(maskfile_contents: &'a String) -> Parser<'a> {
+/// Applies a `key: value` item of a SETTINGS list to the command
+fn apply_setting(cmd: &mut Command, setting: &str, span: Span) -> Option<Diagnostic> {
+    let mut setting_split = setting.splitn(2, ':');
+    let key = setting_split.next().unwrap_or("").trim();
+    let val = setting_split.next().unwrap_or("").trim();
+
+    match key {
+        "aliases" => cmd.aliases = parse_list_setting(val),
+        "hidden" => match parse_bool_setting(key, val, span) {
+            Ok(hidden) => cmd.hidden = hidden,
+            Err(diagnostic) => return Some(diagnostic),
+        },
+        "default" => match parse_bool_setting(key, val, span) {
+            Ok(default) => cmd.default = default,
+            Err(diagnostic) => return Some(diagnostic),
+        },
+        "prompt" => match parse_bool_setting(key, val, span) {
+            Ok(prompt) => cmd.prompt = prompt,
+            Err(diagnostic) => return Some(diagnostic),
+        },
+        "confirm" => {
+            let message = ['"', '\'']
+                .iter()
+                .find_map(|q| val.strip_prefix(*q)?.strip_suffix(*q))
+                .unwrap_or(val);
+            cmd.confirm = Some(message.to_string());
+        }
+        "timeout" => match parse_duration_setting(key, val, span) {
+            Ok(_) => cmd.timeout = Some(val.to_string()),
+            Err(diagnostic) => return Some(diagnostic),
+        },
+        "retries" => match val.parse() {
+            Ok(retries) => cmd.retries = retries,
+            Err(_) => {
+                return Some(Diagnostic::warning(
+                    span,
+                    format!("setting `{}` expects a number, found `{}`", key, val),
+                ))
+            }
+        },
+        "retry_delay" => match parse_duration_setting(key, val, span) {
+            Ok(_) => cmd.retry_delay = Some(val.to_string()),
+            Err(diagnostic) => return Some(diagnostic),
+        },
+        "retry_backoff" => match parse_bool_setting(key, val, span) {
+            Ok(retry_backoff) => cmd.retry_backoff = retry_backoff,
+            Err(diagnostic) => return Some(diagnostic),
+        },
+        "setup" => match parse_bool_setting(key, val, span) {
+            Ok(setup) => cmd.setup = setup,
+            Err(diagnostic) => return Some(diagnostic),
+        },
+        "requires" => {
+            let (valid, invalid): (Vec<&str>, Vec<&str>) = val
+                .split(',')
+                .map(str::trim)
+                .filter(|requirement| !requirement.is_empty())
+                .partition(|requirement| Requirement::parse(requirement).is_some());
+            cmd.requires = valid.into_iter().map(str::to_string).collect();
+            if let Some(requirement) = invalid.first() {
+                return Some(Diagnostic::warning(
+                    span,
+                    format!(
+                        "setting `requires` expects programs like `jq` or `node>=18`, found `{}`",
+                        requirement
+                    ),
+                ));
+            }
+        }
+        "sources" => cmd.sources = parse_list_setting(val),
+        "outputs" => cmd.outputs = parse_list_setting(val),
+        "watch" => cmd.watch = parse_list_setting(val),
+        // The checks are the items of its nested list
+        "preconditions" if val.is_empty() => (),
+        "preconditions" => {
+            return Some(Diagnostic::warning(
+                span,
+                "setting `preconditions` expects a nested list of checks".to_string(),
+            ));
+        }
+        _ => {
+            return Some(Diagnostic::warning(
+                span,
+                format!(
+                    "unknown setting `{}`, expected one of: {}",
+                    key,
+                    SETTINGS_ORDER.join(", ")
+                ),
+            ))
+        }
+    }
+
+    None
+}
+
+/// Splits a setting's value on commas, like `src/**/*.rs, Cargo.toml`
+fn parse_list_setting(val: &str) -> Vec<String> {
+    val.split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// A bare key like `* hidden` is the same as `* hidden: true`
+fn parse_bool_setting(key: &str, val: &str, span: Span) -> Result<bool, Diagnostic> {
+    match val {
+        "" | "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(Diagnostic::warning(
+            span,
+            format!("setting `{}` expects true or false, found `{}`", key, val),
+        )),
+    }
+}
+
+fn parse_duration_setting(key: &str, val: &str, span: Span) -> Result<Duration, Diagnostic> {
+    humantime::parse_duration(val).map_err(|_| {
+        Diagnostic::warning(
+            span,
+            format!(
+                "setting `{}` expects a duration like 10m or 1h 30m, found `{}`",
+                key, val
+            ),
+        )
+    })
+}
+
+fn create_markdown_parser(maskfile_contents: &str) -> Parser<'_> {
     // Set up options and parser. Strikethroughs are not part of the CommonMark standard
     // and we therefore must enable it explicitly.
     let mut options = Options::empty();
     options.insert(Options::ENABLE_STRIKETHROUGH);
-    let parser = Parser::new_ext(&maskfile_contents, options);
+    let parser = Parser::new_ext(maskfile_contents, options);
     parser
 }
 
-fn treeify_commands(commands: Vec<Command>) -> Vec<Command> {
+/// Converts byte ranges of the maskfile source into spans with line numbers
+struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    fn new(contents: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(contents.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { line_starts }
+    }
+
+    fn span(&self, range: &Range<usize>) -> Span {
+        let line = match self.line_starts.binary_search(&range.start) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        };
+        Span {
+            start: range.start,
+            end: range.end,
+            line,
+        }
+    }
+}
+
+/// The parts of a code block's info string like `bash os=linux`, `bash norun` or
+/// `bash {cwd="web", env="NODE_ENV=production"}`
+struct CodeBlockInfo {
+    lang: String,
+    os: Option<String>,
+    norun: bool,
+    finally: bool,
+    attributes: BTreeMap<String, String>,
+}
+
+impl CodeBlockInfo {
+    fn parse(info: &str) -> Self {
+        let info = info.trim_start();
+        let lang_end = info
+            .find(|c: char| c.is_whitespace() || c == '{')
+            .unwrap_or(info.len());
+        let (lang, rest) = info.split_at(lang_end);
+        // A code block without a lang code can still have attributes
+        let (lang, rest) = if ["norun", "finally"].contains(&lang) || lang.contains('=') {
+            ("", info)
+        } else {
+            (lang, rest)
+        };
+
+        let mut os = default_os(lang).map(|os| os.to_string());
+        let mut norun = false;
+        let mut finally = false;
+        let mut attributes = BTreeMap::new();
+        for (key, val) in parse_attributes(rest) {
+            match (key.as_str(), val) {
+                ("norun", None) => norun = true,
+                ("finally", None) => finally = true,
+                ("os", Some(val)) => os = Some(val),
+                (_, Some(val)) => {
+                    attributes.insert(key, val);
+                }
+                _ => (),
+            }
+        }
+
+        Self {
+            lang: lang.to_string(),
+            os,
+            norun,
+            finally,
+            attributes,
+        }
+    }
+
+    fn is_example(&self) -> bool {
+        self.norun || is_doc_lang(&self.lang)
+    }
+}
+
+/// Parses `key=value` pairs and bare keys, which can be wrapped in braces and separated by commas
+/// or whitespace. Values can be quoted with double or single quotes so they can contain spaces and
+/// commas.
+fn parse_attributes(attributes: &str) -> Vec<(String, Option<String>)> {
+    let is_separator = |c: char| c.is_whitespace() || matches!(c, ',' | '{' | '}');
+    let mut parsed = vec![];
+    let mut chars = attributes.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if is_separator(c) {
+            chars.next();
+            continue;
+        }
+
+        let mut key = String::new();
+        while let Some(&c) = chars.peek() {
+            if is_separator(c) || c == '=' {
+                break;
+            }
+            key.push(c);
+            chars.next();
+        }
+
+        let mut val = None;
+        if chars.peek() == Some(&'=') {
+            chars.next();
+            let mut v = String::new();
+            if let Some(quote) = chars.next_if(|c| *c == '"' || *c == '\'') {
+                v.extend(chars.by_ref().take_while(|c| *c != quote));
+            } else {
+                while let Some(&c) = chars.peek() {
+                    if is_separator(c) {
+                        break;
+                    }
+                    v.push(c);
+                    chars.next();
+                }
+            }
+            val = Some(v);
+        }
+
+        if !key.is_empty() {
+            parsed.push((key, val));
+        }
+    }
+
+    parsed
+}
+
+/// Lang codes of code blocks that can't be run, so they're always treated as examples
+pub(crate) fn is_doc_lang(lang: &str) -> bool {
+    DOC_LANGS.contains(&lang)
+}
+
+const DOC_LANGS: [&str; 16] = [
+    "text",
+    "txt",
+    "plaintext",
+    "console",
+    "output",
+    "json",
+    "yaml",
+    "yml",
+    "toml",
+    "xml",
+    "html",
+    "csv",
+    "ini",
+    "diff",
+    "markdown",
+    "md",
+];
+
+/// Windows-only executors run on windows even when no OS is given
+pub(crate) fn default_os(executor: &str) -> Option<&'static str> {
+    match executor {
+        "powershell" | "batch" | "cmd" => Some("windows"),
+        _ => None,
+    }
+}
+
+/// Inline formatting like emphasis or links within a heading, paragraph or list item
+fn is_inline(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link { .. } | Tag::Image { .. }
+    )
+}
+
+fn treeify_commands(commands: Vec<Command>, target_os: Option<&str>) -> Vec<Command> {
     let mut command_tree = vec![];
     let mut current_command = commands.first().expect("command should exist").clone();
-    let num_commands = commands.len();
 
-    for i in 0..num_commands {
-        let mut c = commands[i].clone();
+    for (i, c) in commands.iter().enumerate() {
+        let mut c = c.clone();
 
         // This must be a subcommand
         if c.level > current_command.level {
@@ -221,13 +707,19 @@ fn treeify_commands(commands: Vec<Command>) -> Vec<Command> {
     // Treeify all subcommands recursively
     for c in &mut command_tree {
         if !c.subcommands.is_empty() {
-            c.subcommands = treeify_commands(c.subcommands.clone());
+            c.subcommands = treeify_commands(c.subcommands.clone(), target_os);
         }
     }
 
-    // the command or any one of its subcommands must have script to be included in the tree
-    // root level commands must be retained
-    command_tree.retain(|c| c.script.is_some() || !c.subcommands.is_empty() || c.level == 1);
+    // the command or any one of its subcommands must have a script for the target OS to be
+    // included in the tree. root level commands must be retained
+    command_tree.retain(|c| {
+        let has_script = match target_os {
+            Some(os) => !c.scripts_for(os).is_empty(),
+            None => !c.scripts.is_empty(),
+        };
+        has_script || !c.subcommands.is_empty() || c.level == 1
+    });
 
     command_tree
 }
@@ -236,7 +728,7 @@ fn parse_command_name_required_and_optional_args(
     text: String,
 ) -> (String, Vec<RequiredArg>, Vec<OptionalArg>) {
     // Checks if any args are present and if not, return early
-    let split_idx = match text.find(|c| c == '(' || c == '[') {
+    let split_idx = match text.find(['(', '[']) {
         Some(idx) => idx,
         None => return (text.trim().to_string(), vec![], vec![]),
     };
@@ -246,7 +738,7 @@ fn parse_command_name_required_and_optional_args(
 
     // Collects (required_args)
     let required_args = args
-        .split(|c| c == '(' || c == ')')
+        .split(['(', ')'])
         .filter_map(|arg| match arg.trim() {
             a if !a.is_empty() && !a.contains('[') => Some(RequiredArg::new(a.trim().to_string())),
             _ => None,
@@ -255,7 +747,7 @@ fn parse_command_name_required_and_optional_args(
 
     // Collects [optional_args]
     let optional_args = args
-        .split(|c| c == '[' || c == ']')
+        .split(['[', ']'])
         .filter_map(|arg| match arg.trim() {
             a if !a.is_empty() && !a.contains('(') => Some(OptionalArg::new(a.trim().to_string())),
             _ => None,
@@ -342,8 +834,46 @@ mod parse {
             "multiple": false,
             "takes_value": false,
             "required": false,
+            "secret": false,
             "validate_as_number": false,
             "choices": [],
+            "span": { "start": 0, "end": 0, "line": 0 },
+        });
+
+        let serve_script = json!({
+            "executor": "bash",
+            "finally": false,
+            "source": "echo \"Serving on port $port\"\n",
+            "span": { "start": 117, "end": 157, "line": 10 },
+            "os": null,
+            "attributes": {},
+        });
+
+        let node_script = json!({
+            "executor": "js",
+            "finally": false,
+            "source": "const { name } = process.env;\nconsole.log(`Hello, ${name}!`);\n",
+            "span": { "start": 235, "end": 306, "line": 20 },
+            "os": null,
+            "attributes": {},
+        });
+
+        let subcommand_script = json!({
+            "executor": "bash",
+            "finally": false,
+            "source": "echo hey\n",
+            "span": { "start": 364, "end": 384, "line": 29 },
+            "os": null,
+            "attributes": {},
+        });
+
+        let multi_script = json!({
+            "executor": "bash",
+            "finally": false,
+            "source": "if ! [ -z \"$optional\" ]; then\n echo \"This is optional - $optional\"\nfi\n\necho \"This is required - $required\"\n",
+            "span": { "start": 498, "end": 616, "line": 41 },
+            "os": null,
+            "attributes": {},
         });
 
         assert_eq!(
@@ -353,16 +883,31 @@ mod parse {
                 "commands": [
                     {
                         "level": 2,
+                        "span": { "start": 69, "end": 85, "line": 6 },
                         "name": "serve",
                         "description": "Serve the app on the `port`",
-                        "script": {
-                            "executor": "bash",
-                            "source": "echo \"Serving on port $port\"\n",
-                        },
+                        "aliases": [],
+                        "hidden": false,
+                        "default": false,
+                        "prompt": false,
+                        "confirm": null,
+                        "timeout": null,
+                        "retries": 0,
+                        "retry_delay": null,
+                        "retry_backoff": false,
+                        "setup": false,
+                        "requires": [],
+                        "preconditions": [],
+                        "sources": [],
+                        "outputs": [],
+                        "watch": [],
+                        "scripts": [serve_script],
+                        "examples": [],
                         "subcommands": [],
                         "required_args": [
                             {
-                                "name": "port"
+                                "name": "port",
+                                "span": { "start": 79, "end": 83, "line": 6 },
                             }
                         ],
                         "optional_args": [],
@@ -370,16 +915,31 @@ mod parse {
                     },
                     {
                         "level": 2,
+                        "span": { "start": 159, "end": 174, "line": 14 },
                         "name": "node",
                         "description": "An example node script",
-                        "script": {
-                            "executor": "js",
-                            "source": "const { name } = process.env;\nconsole.log(`Hello, ${name}!`);\n",
-                        },
+                        "aliases": [],
+                        "hidden": false,
+                        "default": false,
+                        "prompt": false,
+                        "confirm": null,
+                        "timeout": null,
+                        "retries": 0,
+                        "retry_delay": null,
+                        "retry_backoff": false,
+                        "setup": false,
+                        "requires": [],
+                        "preconditions": [],
+                        "sources": [],
+                        "outputs": [],
+                        "watch": [],
+                        "scripts": [node_script],
+                        "examples": [],
                         "subcommands": [],
                         "required_args": [
                             {
-                                "name": "name"
+                                "name": "name",
+                                "span": { "start": 168, "end": 172, "line": 14 },
                             }
                         ],
                         "optional_args": [],
@@ -387,18 +947,49 @@ mod parse {
                     },
                     {
                         "level": 2,
+                        "span": { "start": 308, "end": 318, "line": 25 },
                         "name": "parent",
                         "description": "",
-                        "script": null,
+                        "aliases": [],
+                        "hidden": false,
+                        "default": false,
+                        "prompt": false,
+                        "confirm": null,
+                        "timeout": null,
+                        "retries": 0,
+                        "retry_delay": null,
+                        "retry_backoff": false,
+                        "setup": false,
+                        "requires": [],
+                        "preconditions": [],
+                        "sources": [],
+                        "outputs": [],
+                        "watch": [],
+                        "scripts": [],
+                        "examples": [],
                         "subcommands": [
                             {
                                 "level": 3,
+                                "span": { "start": 318, "end": 340, "line": 26 },
                                 "name": "subcommand",
                                 "description": "This is a subcommand",
-                                "script": {
-                                    "executor": "bash",
-                                    "source": "echo hey\n",
-                                },
+                                "aliases": [],
+                                "hidden": false,
+                                "default": false,
+                                "prompt": false,
+                                "confirm": null,
+                                "timeout": null,
+                                "retries": 0,
+                                "retry_delay": null,
+                                "retry_backoff": false,
+                                "setup": false,
+                                "requires": [],
+                                "preconditions": [],
+                                "sources": [],
+                                "outputs": [],
+                                "watch": [],
+                                "scripts": [subcommand_script],
+                                "examples": [],
                                 "subcommands": [],
                                 "optional_args": [],
                                 "required_args": [],
@@ -411,15 +1002,35 @@ mod parse {
                     },
                     {
                         "level": 2,
+                        "span": { "start": 436, "end": 467, "line": 37 },
                         "name": "multi",
                         "description": "Example with optional args",
-                        "script": {
-                            "executor": "bash",
-                            "source": "if ! [ -z \"$optional\" ]; then\n echo \"This is optional - $optional\"\nfi\n\necho \"This is required - $required\"\n",
-                        },
+                        "aliases": [],
+                        "hidden": false,
+                        "default": false,
+                        "prompt": false,
+                        "confirm": null,
+                        "timeout": null,
+                        "retries": 0,
+                        "retry_delay": null,
+                        "retry_backoff": false,
+                        "setup": false,
+                        "requires": [],
+                        "preconditions": [],
+                        "sources": [],
+                        "outputs": [],
+                        "watch": [],
+                        "scripts": [multi_script],
+                        "examples": [],
                         "subcommands": [],
-                        "required_args": [{ "name": "required" }],
-                        "optional_args": [{ "name": "optional" }],
+                        "required_args": [{
+                            "name": "required",
+                            "span": { "start": 446, "end": 454, "line": 37 },
+                        }],
+                        "optional_args": [{
+                            "name": "optional",
+                            "span": { "start": 457, "end": 465, "line": 37 },
+                        }],
                         "named_flags": [verbose_flag],
                     }
                 ]
@@ -428,3 +1039,656 @@ mod parse {
         );
     }
 }
+
+#[cfg(test)]
+mod spans {
+    use super::*;
+
+    #[test]
+    fn records_the_location_of_named_flags() {
+        let contents = "## serve\n\n**OPTIONS**\n* port\n    * flags: --port\n* host\n    * flags: --host\n\n~~~sh\necho\n~~~\n";
+        let maskfile = parse(contents.to_string());
+        let flags = &maskfile.commands[0].named_flags;
+
+        assert_eq!(4, flags[0].span.line);
+        assert!(contents[flags[0].span.start..flags[0].span.end].starts_with("* port"));
+        assert_eq!(6, flags[1].span.line);
+        assert!(contents[flags[1].span.start..flags[1].span.end].starts_with("* host"));
+        // The verbose flag is auto added so it has no location
+        assert_eq!(Span::default(), flags[2].span);
+    }
+}
+
+#[cfg(test)]
+mod commonmark {
+    use super::*;
+
+    fn parse_commands(contents: &str) -> Vec<Command> {
+        parse(contents.to_string()).without_spans().commands
+    }
+
+    #[test]
+    fn setext_headings() {
+        let commands = parse_commands(
+            r#"
+Tasks
+=====
+
+build (target)
+--------------
+
+> Build the target
+
+~~~sh
+echo "building $target"
+~~~
+"#,
+        );
+
+        assert_eq!(1, commands.len());
+        assert_eq!("build", commands[0].name);
+        assert_eq!(2, commands[0].level);
+        assert_eq!("target", commands[0].required_args[0].name);
+        assert_eq!("Build the target", commands[0].description);
+    }
+
+    #[test]
+    fn headings_with_emphasis_and_links() {
+        let commands = parse_commands(
+            r#"
+## **deploy** (env)
+
+~~~sh
+echo "deploying to $env"
+~~~
+
+## [docs](https://example.com) *serve*
+
+~~~sh
+echo "serving docs"
+~~~
+"#,
+        );
+
+        assert_eq!("deploy", commands[0].name);
+        assert_eq!("env", commands[0].required_args[0].name);
+        assert_eq!("docs serve", commands[1].name);
+    }
+
+    #[test]
+    fn indented_code_blocks_are_documentation() {
+        let commands = parse_commands(
+            r#"
+## build
+
+Run it like this:
+
+    mask build
+
+~~~sh
+echo "building"
+~~~
+"#,
+        );
+
+        assert_eq!(1, commands[0].scripts.len());
+        let script = &commands[0].scripts[0];
+        assert_eq!("sh", script.executor);
+        assert_eq!("echo \"building\"\n", script.source);
+    }
+
+    #[test]
+    fn code_blocks_inside_lists_are_documentation() {
+        let maskfile = parse(
+            r#"
+## build
+
+**OPTIONS**
+* release
+    * flags: --release
+    * desc: Build in release mode
+
+1. Install the dependencies first:
+
+   ~~~sh
+   npm install
+   ~~~
+
+~~~sh
+echo "building"
+~~~
+"#
+            .to_string(),
+        );
+        let build = &maskfile.commands[0];
+
+        assert_eq!("echo \"building\"\n", build.scripts[0].source);
+        assert_eq!("release", build.named_flags[0].name);
+        assert_eq!("Build in release mode", build.named_flags[0].description);
+        assert_eq!(1, maskfile.diagnostics.len());
+        assert_eq!(11, maskfile.diagnostics[0].span.line);
+    }
+
+    #[test]
+    fn nested_blockquotes_are_part_of_the_description() {
+        let commands = parse_commands(
+            r#"
+## build
+
+> Build the project
+> for production
+>
+> > Requires node
+
+~~~sh
+echo "building"
+~~~
+"#,
+        );
+
+        assert_eq!(
+            "Build the project for production Requires node",
+            commands[0].description
+        );
+    }
+}
+
+#[cfg(test)]
+mod scripts {
+    use super::*;
+
+    const CONTENTS: &str = r#"
+## build
+
+~~~sh
+echo "setting up"
+~~~
+
+~~~python
+print("building anywhere")
+~~~
+
+~~~zsh os=macos
+echo "building on macos"
+~~~
+
+~~~powershell
+Write-Output "building on windows"
+~~~
+"#;
+
+    fn executors(scripts: Vec<&Script>) -> Vec<&str> {
+        scripts.iter().map(|s| s.executor.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_every_code_block_in_order() {
+        let build = &parse(CONTENTS.to_string()).commands[0];
+        let os: Vec<Option<&str>> = build.scripts.iter().map(|s| s.os.as_deref()).collect();
+
+        assert_eq!(
+            vec!["sh", "python", "zsh", "powershell"],
+            executors(build.scripts.iter().collect())
+        );
+        assert_eq!(vec![None, None, Some("macos"), Some("windows")], os);
+        assert_eq!("print(\"building anywhere\")\n", build.scripts[1].source);
+    }
+
+    #[test]
+    fn selects_the_scripts_for_the_target_os() {
+        let build = &parse(CONTENTS.to_string()).commands[0];
+
+        assert_eq!(vec!["sh", "python"], executors(build.scripts_for("linux")));
+        assert_eq!(vec!["zsh"], executors(build.scripts_for("macos")));
+        assert_eq!(vec!["powershell"], executors(build.scripts_for("windows")));
+    }
+
+    #[test]
+    fn leaves_out_commands_that_cant_run_on_the_target_os() {
+        let contents = r#"
+## build
+
+```sh
+make
+```
+
+```powershell
+msbuild
+```
+
+## clean
+
+```powershell
+Remove-Item dist
+```
+"#;
+        let names = |target_os: &str| -> Vec<String> {
+            let options = ParseOptions {
+                target_os: Some(target_os.to_string()),
+            };
+            parse_with_options(contents.to_string(), &options)
+                .commands
+                .into_iter()
+                .map(|c| c.name)
+                .collect()
+        };
+
+        assert_eq!(vec!["build"], names("linux"));
+        assert_eq!(vec!["build", "clean"], names("windows"));
+    }
+
+    #[test]
+    fn keeps_the_scripts_for_every_os() {
+        let contents = "## build\n\n```sh\nmake\n```\n\n```powershell\nmsbuild\n```\n";
+        let options = ParseOptions {
+            target_os: Some("linux".to_string()),
+        };
+        let build = &parse_with_options(contents.to_string(), &options).commands[0];
+
+        assert_eq!(
+            vec!["sh", "powershell"],
+            executors(build.scripts.iter().collect())
+        );
+    }
+
+    #[test]
+    fn keeps_commands_for_every_os_without_a_target_os() {
+        let contents = "## clean\n\n```powershell\nRemove-Item dist\n```\n";
+        let options = ParseOptions { target_os: None };
+        let maskfile = parse_with_options(contents.to_string(), &options);
+
+        assert_eq!("clean", maskfile.commands[0].name);
+        assert_eq!(contents, maskfile.to_markdown());
+    }
+}
+
+#[cfg(test)]
+mod examples {
+    use super::*;
+
+    #[test]
+    fn keeps_code_blocks_that_dont_run_as_examples() {
+        let build = parse(
+            r#"
+## build
+
+```json
+{ "target": "release" }
+```
+
+```bash norun
+mask build
+```
+
+```norun
+some output
+```
+
+```bash
+echo "building"
+```
+"#
+            .to_string(),
+        )
+        .commands[0]
+            .clone();
+
+        let examples: Vec<(&str, &str)> = build
+            .examples
+            .iter()
+            .map(|e| (e.lang.as_str(), e.source.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("json", "{ \"target\": \"release\" }\n"),
+                ("bash", "mask build\n"),
+                ("", "some output\n"),
+            ],
+            examples
+        );
+        assert_eq!(1, build.scripts.len());
+        assert_eq!("echo \"building\"\n", build.scripts[0].source);
+    }
+
+    #[test]
+    fn commands_with_only_examples_are_documentation() {
+        let maskfile = parse("## config\n\n```yaml\nport: 8080\n```\n".to_string());
+
+        assert!(maskfile.commands.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod attributes {
+    use super::*;
+
+    fn parse_info(info: &str) -> Script {
+        let contents = format!("## build\n\n```{}\necho building\n```\n", info);
+        parse(contents).commands[0].scripts[0].clone()
+    }
+
+    #[test]
+    fn parses_attributes_from_the_info_string() {
+        let script = parse_info(r#"bash {cwd="web", env="NODE_ENV=production", timeout="5m"}"#);
+
+        assert_eq!("bash", script.executor);
+        assert_eq!(
+            vec![
+                ("cwd", "web"),
+                ("env", "NODE_ENV=production"),
+                ("timeout", "5m")
+            ],
+            script
+                .attributes
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn parses_finally_scripts() {
+        let script = parse_info("sh finally {cwd=web}");
+
+        assert_eq!("sh", script.executor);
+        assert!(script.finally);
+        assert_eq!(Some(&"web".to_string()), script.attributes.get("cwd"));
+        assert!(!parse_info("sh").finally);
+    }
+
+    #[test]
+    fn values_can_be_quoted_or_bare() {
+        let script = parse_info(r#"bash{env="A=1, B=2" msg='say "hi"' cwd=web}"#);
+
+        assert_eq!("bash", script.executor);
+        assert_eq!("A=1, B=2", script.attributes["env"]);
+        assert_eq!("say \"hi\"", script.attributes["msg"]);
+        assert_eq!("web", script.attributes["cwd"]);
+    }
+
+    #[test]
+    fn os_can_be_given_as_an_attribute() {
+        let contents = "## build\n\n```zsh {os=\"macos\", cwd=\"app\"}\necho building\n```\n";
+        let options = ParseOptions {
+            target_os: Some("macos".to_string()),
+        };
+        let script = &parse_with_options(contents.to_string(), &options).commands[0].scripts[0];
+
+        assert_eq!(Some("macos".to_string()), script.os);
+        assert!(!script.attributes.contains_key("os"));
+    }
+
+    #[test]
+    fn norun_can_be_given_as_an_attribute() {
+        let contents = "## build\n\n```bash {norun}\nmask build\n```\n\n```sh\necho\n```\n";
+        let build = &parse(contents.to_string()).commands[0];
+
+        assert_eq!("bash", build.examples[0].lang);
+        assert_eq!(1, build.scripts.len());
+    }
+}
+
+#[cfg(test)]
+mod settings {
+    use super::*;
+
+    #[test]
+    fn parses_aliases() {
+        let maskfile = parse(
+            r#"
+## test
+
+**SETTINGS**
+* aliases: t, tst
+
+~~~sh
+echo "testing"
+~~~
+"#
+            .to_string(),
+        );
+
+        assert_eq!(vec!["t", "tst"], maskfile.commands[0].aliases);
+        assert!(maskfile.commands[0].has_name("tst"));
+        assert!(maskfile.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn parses_hidden() {
+        let parse_hidden = |setting: &str| {
+            let contents = format!(
+                "## test\n\n**SETTINGS**\n* {}\n\n~~~sh\necho\n~~~\n",
+                setting
+            );
+            let maskfile = parse(contents);
+            (maskfile.commands[0].hidden, maskfile.diagnostics.len())
+        };
+
+        assert_eq!((true, 0), parse_hidden("hidden"));
+        assert_eq!((true, 0), parse_hidden("hidden: true"));
+        assert_eq!((false, 0), parse_hidden("hidden: false"));
+        assert_eq!((false, 1), parse_hidden("hidden: yes"));
+    }
+
+    #[test]
+    fn parses_default() {
+        let maskfile = parse(
+            "## dev\n\n**SETTINGS**\n* default\n\n~~~sh\necho\n~~~\n\n## build\n\n~~~sh\necho\n~~~\n"
+                .to_string(),
+        );
+
+        assert!(maskfile.commands[0].default);
+        assert!(!maskfile.commands[1].default);
+        assert!(maskfile.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn parses_prompt_and_secret_flags() {
+        let maskfile = parse(
+            "## login\n\n**SETTINGS**\n* prompt\n\n**OPTIONS**\n* token\n    * flags: --token\n    * type: string\n    * secret\n\n~~~sh\necho\n~~~\n"
+                .to_string(),
+        );
+
+        let login = &maskfile.commands[0];
+        assert!(login.prompt);
+        assert!(login.named_flags[0].secret);
+        assert!(maskfile.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn parses_confirm() {
+        let parse_confirm = |setting: &str| {
+            let contents = format!(
+                "## reset\n\n**SETTINGS**\n* {}\n\n~~~sh\necho\n~~~\n",
+                setting
+            );
+            parse(contents).commands[0].confirm.clone()
+        };
+
+        assert_eq!(Some("".to_string()), parse_confirm("confirm"));
+        assert_eq!(
+            Some("Drop the db?".to_string()),
+            parse_confirm("confirm: Drop the db?")
+        );
+        assert_eq!(
+            Some("Drop the db?".to_string()),
+            parse_confirm("confirm: \"Drop the db?\"")
+        );
+    }
+
+    #[test]
+    fn parses_timeout() {
+        let parse_timeout = |setting: &str| {
+            let contents = format!(
+                "## test\n\n**SETTINGS**\n* {}\n\n~~~sh\necho\n~~~\n",
+                setting
+            );
+            let maskfile = parse(contents);
+            (
+                maskfile.commands[0].timeout.clone(),
+                maskfile.diagnostics.len(),
+            )
+        };
+
+        assert_eq!((Some("10m".to_string()), 0), parse_timeout("timeout: 10m"));
+        assert_eq!(
+            (Some("1h 30m".to_string()), 0),
+            parse_timeout("timeout: 1h 30m")
+        );
+        assert_eq!((None, 1), parse_timeout("timeout: forever"));
+    }
+
+    #[test]
+    fn parses_retries() {
+        let maskfile = parse(
+            "## test\n\n**SETTINGS**\n* retries: 3\n* retry_delay: 5s\n* retry_backoff\n\n~~~sh\necho\n~~~\n"
+                .to_string(),
+        );
+
+        let test = &maskfile.commands[0];
+        assert_eq!(3, test.retries);
+        assert_eq!(Some("5s".to_string()), test.retry_delay);
+        assert!(test.retry_backoff);
+        assert!(maskfile.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn warns_about_invalid_retries() {
+        let maskfile =
+            parse("## test\n\n**SETTINGS**\n* retries: -1\n\n~~~sh\necho\n~~~\n".to_string());
+
+        assert_eq!(0, maskfile.commands[0].retries);
+        assert_eq!(
+            "setting `retries` expects a number, found `-1`",
+            maskfile.diagnostics[0].message
+        );
+    }
+
+    #[test]
+    fn parses_setup() {
+        let maskfile = parse(
+            "## docker\n\n**SETTINGS**\n* setup\n\n~~~sh\necho\n~~~\n\n### docker push\n\n~~~sh\necho\n~~~\n"
+                .to_string(),
+        );
+
+        let docker = &maskfile.commands[0];
+        assert!(docker.setup);
+        assert!(!docker.subcommands[0].setup);
+        assert!(maskfile.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn parses_requires_and_preconditions() {
+        let maskfile = parse(
+            r#"## deploy
+
+**SETTINGS**
+* requires: docker, jq, node>=18
+* preconditions:
+    * `test -f .env`: Copy .env.example to .env first
+    * `docker info`
+    * test -n "$TOKEN"
+
+~~~sh
+echo
+~~~
+"#
+            .to_string(),
+        );
+
+        let deploy = &maskfile.commands[0];
+        assert_eq!(vec!["docker", "jq", "node>=18"], deploy.requires);
+        let preconditions: Vec<(&str, Option<&str>, usize)> = deploy
+            .preconditions
+            .iter()
+            .map(|p| (p.check.as_str(), p.message.as_deref(), p.span.line))
+            .collect();
+        assert_eq!(
+            vec![
+                ("test -f .env", Some("Copy .env.example to .env first"), 6),
+                ("docker info", None, 7),
+                ("test -n \"$TOKEN\"", None, 8),
+            ],
+            preconditions
+        );
+        assert!(maskfile.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn parses_requirements() {
+        assert_eq!(
+            Some(Requirement {
+                program: "jq",
+                version: None
+            }),
+            Requirement::parse("jq")
+        );
+        assert_eq!(
+            Some(Requirement {
+                program: "node",
+                version: Some((">=", "18.2"))
+            }),
+            Requirement::parse("node >= 18.2")
+        );
+        assert_eq!(
+            Some(Requirement {
+                program: "go",
+                version: Some(("<", "2"))
+            }),
+            Requirement::parse("go<2")
+        );
+        assert_eq!(None, Requirement::parse("node>=latest"));
+        assert_eq!(None, Requirement::parse(">=18"));
+        assert_eq!(None, Requirement::parse("node js"));
+    }
+
+    #[test]
+    fn warns_about_invalid_requires() {
+        let maskfile = parse(
+            "## test\n\n**SETTINGS**\n* requires: jq, node>=latest\n\n~~~sh\necho\n~~~\n"
+                .to_string(),
+        );
+
+        assert_eq!(vec!["jq"], maskfile.commands[0].requires);
+        assert_eq!(
+            "setting `requires` expects programs like `jq` or `node>=18`, found `node>=latest`",
+            maskfile.diagnostics[0].message
+        );
+    }
+
+    #[test]
+    fn parses_sources_and_outputs() {
+        let maskfile = parse(
+            "## build\n\n**SETTINGS**\n* sources: src/**/*.rs, Cargo.toml\n* outputs: target/release/app\n\n~~~sh\necho\n~~~\n"
+                .to_string(),
+        );
+
+        let build = &maskfile.commands[0];
+        assert_eq!(vec!["src/**/*.rs", "Cargo.toml"], build.sources);
+        assert_eq!(vec!["target/release/app"], build.outputs);
+        assert!(maskfile.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn parses_watch() {
+        let maskfile = parse(
+            "## docs\n\n**SETTINGS**\n* watch: docs/**, mkdocs.yml\n\n~~~sh\necho\n~~~\n"
+                .to_string(),
+        );
+
+        assert_eq!(vec!["docs/**", "mkdocs.yml"], maskfile.commands[0].watch);
+        assert!(maskfile.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn warns_about_unknown_settings() {
+        let maskfile =
+            parse("## test\n\n**SETTINGS**\n* nope: 1\n\n~~~sh\necho\n~~~\n".to_string());
+
+        assert_eq!(1, maskfile.diagnostics.len());
+        assert_eq!(4, maskfile.diagnostics[0].span.line);
+        assert!(maskfile.diagnostics[0]
+            .message
+            .starts_with("unknown setting `nope`"));
+    }
+}
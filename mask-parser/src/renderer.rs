@@ -0,0 +1,503 @@
+use crate::maskfile::*;
+use crate::parser::{default_os, is_doc_lang};
+
+pub fn render(maskfile: &Maskfile) -> String {
+    let mut blocks = vec![];
+
+    if !maskfile.title.is_empty() {
+        blocks.push(format!("# {}", maskfile.title));
+    }
+    if !maskfile.description.is_empty() {
+        blocks.push(render_description(&maskfile.description));
+    }
+
+    for c in &maskfile.commands {
+        render_command(&mut blocks, c, "");
+    }
+
+    let mut markdown = blocks.join("\n\n");
+    markdown.push('\n');
+    markdown
+}
+
+fn render_command(blocks: &mut Vec<String>, cmd: &Command, parent_name: &str) {
+    // Always use the full name so subcommands can't be mistaken for siblings
+    let name = if parent_name.is_empty() {
+        cmd.name.clone()
+    } else {
+        format!("{} {}", parent_name, cmd.name)
+    };
+
+    blocks.push(render_heading(cmd, &name));
+
+    if !cmd.description.is_empty() {
+        blocks.push(render_description(&cmd.description));
+    }
+
+    if let Some(settings) = render_settings(cmd) {
+        blocks.push(settings);
+    }
+
+    // The verbose flag is added automatically when parsing, so leave it out
+    let flags: Vec<&NamedFlag> = cmd
+        .named_flags
+        .iter()
+        .filter(|f| **f != NamedFlag::verbose())
+        .collect();
+    if !flags.is_empty() {
+        blocks.push(render_named_flags(&flags));
+    }
+
+    blocks.extend(cmd.examples.iter().map(render_example));
+    blocks.extend(cmd.scripts.iter().map(render_script));
+
+    for c in &cmd.subcommands {
+        render_command(blocks, c, &name);
+    }
+}
+
+fn render_heading(cmd: &Command, name: &str) -> String {
+    let mut heading = format!("{} {}", "#".repeat(cmd.level as usize), name);
+    for arg in &cmd.required_args {
+        heading += &format!(" ({})", arg.name);
+    }
+    for arg in &cmd.optional_args {
+        heading += &format!(" [{}]", arg.name);
+    }
+    heading
+}
+
+fn render_description(description: &str) -> String {
+    format!("> {}", description)
+}
+
+/// The order that settings are rendered in
+pub const SETTINGS_ORDER: [&str; 15] = [
+    "aliases",
+    "hidden",
+    "default",
+    "prompt",
+    "confirm",
+    "timeout",
+    "retries",
+    "retry_delay",
+    "retry_backoff",
+    "setup",
+    "requires",
+    "sources",
+    "outputs",
+    "watch",
+    "preconditions",
+];
+
+fn render_settings(cmd: &Command) -> Option<String> {
+    let mut settings = vec![];
+    if !cmd.aliases.is_empty() {
+        settings.push(("aliases", cmd.aliases.join(", ")));
+    }
+    if cmd.hidden {
+        settings.push(("hidden", "true".to_string()));
+    }
+    if cmd.default {
+        settings.push(("default", "true".to_string()));
+    }
+    if cmd.prompt {
+        settings.push(("prompt", "true".to_string()));
+    }
+    if let Some(message) = &cmd.confirm {
+        settings.push(("confirm", message.clone()));
+    }
+    if let Some(timeout) = &cmd.timeout {
+        settings.push(("timeout", timeout.clone()));
+    }
+    if cmd.retries > 0 {
+        settings.push(("retries", cmd.retries.to_string()));
+    }
+    if let Some(retry_delay) = &cmd.retry_delay {
+        settings.push(("retry_delay", retry_delay.clone()));
+    }
+    if cmd.retry_backoff {
+        settings.push(("retry_backoff", "true".to_string()));
+    }
+    if cmd.setup {
+        settings.push(("setup", "true".to_string()));
+    }
+    if !cmd.requires.is_empty() {
+        settings.push(("requires", cmd.requires.join(", ")));
+    }
+    if !cmd.sources.is_empty() {
+        settings.push(("sources", cmd.sources.join(", ")));
+    }
+    if !cmd.outputs.is_empty() {
+        settings.push(("outputs", cmd.outputs.join(", ")));
+    }
+    if !cmd.watch.is_empty() {
+        settings.push(("watch", cmd.watch.join(", ")));
+    }
+
+    if settings.is_empty() && cmd.preconditions.is_empty() {
+        return None;
+    }
+
+    let mut lines = vec!["**SETTINGS**".to_string()];
+    for (key, val) in settings {
+        if val.is_empty() {
+            lines.push(format!("* {}", key));
+        } else {
+            lines.push(format!("* {}: {}", key, val));
+        }
+    }
+    if !cmd.preconditions.is_empty() {
+        lines.push("* preconditions".to_string());
+        for precondition in &cmd.preconditions {
+            match &precondition.message {
+                Some(message) => lines.push(format!("    * `{}`: {}", precondition.check, message)),
+                None => lines.push(format!("    * `{}`", precondition.check)),
+            }
+        }
+    }
+    Some(lines.join("\n"))
+}
+
+fn render_named_flags(flags: &[&NamedFlag]) -> String {
+    let items: Vec<OptionItem> = flags
+        .iter()
+        .map(|flag| {
+            let mut config = vec![];
+
+            let mut flag_names = vec![];
+            if !flag.short.is_empty() {
+                flag_names.push(format!("-{}", flag.short));
+            }
+            if !flag.long.is_empty() {
+                flag_names.push(format!("--{}", flag.long));
+            }
+            if !flag_names.is_empty() {
+                config.push(("flags".to_string(), flag_names.join(" ")));
+            }
+
+            if flag.validate_as_number {
+                config.push(("type".to_string(), "number".to_string()));
+            } else if flag.takes_value {
+                config.push(("type".to_string(), "string".to_string()));
+            }
+
+            if !flag.description.is_empty() {
+                config.push(("desc".to_string(), flag.description.clone()));
+            }
+
+            if !flag.choices.is_empty() {
+                config.push(("choices".to_string(), flag.choices.join(", ")));
+            }
+
+            if flag.required {
+                config.push(("required".to_string(), "".to_string()));
+            }
+
+            if flag.secret {
+                config.push(("secret".to_string(), "".to_string()));
+            }
+
+            OptionItem {
+                name: flag.name.clone(),
+                config,
+            }
+        })
+        .collect();
+
+    render_option_list(&items)
+}
+
+/// The order that option config keys are rendered in. Unknown keys come last.
+pub const OPTION_CONFIG_ORDER: [&str; 6] =
+    ["flags", "type", "desc", "choices", "required", "secret"];
+
+/// A single flag within an OPTIONS list.
+pub struct OptionItem {
+    pub name: String,
+    /// Pairs of config keys and values. An empty value renders only the key.
+    pub config: Vec<(String, String)>,
+}
+
+pub fn render_option_list(items: &[OptionItem]) -> String {
+    let mut lines = vec!["**OPTIONS**".to_string()];
+
+    for item in items {
+        lines.push(format!("* {}", item.name));
+        for (key, val) in &item.config {
+            if val.is_empty() {
+                lines.push(format!("    * {}", key));
+            } else {
+                lines.push(format!("    * {}: {}", key, val));
+            }
+        }
+    }
+
+    lines.join("\n")
+}
+
+pub fn render_code_block(lang_code: &str, source: &str) -> String {
+    // Use a fence that is longer than any backtick fence inside of the source
+    let longest_inner_fence = source
+        .lines()
+        .map(|line| line.trim_start().chars().take_while(|c| *c == '`').count())
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(std::cmp::max(3, longest_inner_fence + 1));
+
+    let mut block = format!("{}{}\n{}", fence, lang_code, source);
+    if !source.is_empty() && !source.ends_with('\n') {
+        block.push('\n');
+    }
+    block += &fence;
+    block
+}
+
+fn render_script(script: &Script) -> String {
+    let mut info = script.executor.clone();
+    // The OS only needs to be given when it's not implied by the executor
+    if let Some(os) = &script.os {
+        if default_os(&script.executor) != Some(os.as_str()) {
+            info += &format!(" os={}", os);
+        }
+    }
+    if script.finally {
+        info += " finally";
+    }
+    if !script.attributes.is_empty() {
+        let attributes: Vec<String> = script
+            .attributes
+            .iter()
+            .map(|(key, val)| {
+                // Values can't be escaped, so values with double quotes use single ones instead
+                let quote = if val.contains('"') { '\'' } else { '"' };
+                format!("{}={}{}{}", key, quote, val, quote)
+            })
+            .collect();
+        info += &format!(" {{{}}}", attributes.join(", "));
+    }
+    render_code_block(info.trim(), &script.source)
+}
+
+fn render_example(example: &Example) -> String {
+    // Examples in a lang that could be run need to be marked so they aren't
+    let info = if is_doc_lang(&example.lang) {
+        example.lang.clone()
+    } else {
+        format!("{} norun", example.lang).trim().to_string()
+    };
+    render_code_block(&info, &example.source)
+}
+
+#[cfg(test)]
+const TEST_MASKFILE: &str = r#"
+# Document Title
+
+> The maskfile description
+
+## serve (port)
+
+> Serve the app on the `port`
+
+**OPTIONS**
+* env
+    * desc: Which environment to use
+    * choices: dev, prod
+    * type: string
+    * flags: --env -e
+    * required
+    * secret
+* timeout
+    * flags: --timeout
+    * type: number
+
+~~~bash
+echo "Serving on port $port"
+~~~
+
+## node (name) [greeting]
+
+> An example node script
+
+```js
+const { name } = process.env;
+console.log(`Hello, ${name}!`);
+```
+
+## parent
+### parent subcommand
+> This is a subcommand
+
+~~~sh
+echo hey
+~~~
+
+#### deeply nested
+
+~~~sh
+echo nested
+~~~
+
+## no_script
+
+This command has no source/script.
+"#;
+
+#[cfg(test)]
+mod to_markdown {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn renders_canonical_markdown() {
+        let maskfile = parse(TEST_MASKFILE.to_string());
+
+        let expected = r#"# Document Title
+
+> The maskfile description
+
+## serve (port)
+
+> Serve the app on the `port`
+
+**OPTIONS**
+* env
+    * flags: -e --env
+    * type: string
+    * desc: Which environment to use
+    * choices: dev, prod
+    * required
+    * secret
+* timeout
+    * flags: --timeout
+    * type: number
+
+```bash
+echo "Serving on port $port"
+```
+
+## node (name) [greeting]
+
+> An example node script
+
+```js
+const { name } = process.env;
+console.log(`Hello, ${name}!`);
+```
+
+## parent
+
+### parent subcommand
+
+> This is a subcommand
+
+```sh
+echo hey
+```
+
+#### parent subcommand deeply nested
+
+```sh
+echo nested
+```
+"#;
+
+        assert_eq!(expected, maskfile.to_markdown());
+    }
+
+    #[test]
+    fn round_trips_to_an_equivalent_maskfile() {
+        let maskfile = parse(TEST_MASKFILE.to_string());
+        let reparsed = parse(maskfile.to_markdown());
+
+        assert_eq!(maskfile.without_spans(), reparsed.without_spans());
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let maskfile = parse(TEST_MASKFILE.to_string());
+        let json = maskfile.to_json().expect("should have serialized to json");
+        let deserialized: Maskfile =
+            serde_json::from_value(json).expect("should have deserialized from json");
+
+        assert_eq!(maskfile, deserialized);
+    }
+
+    #[test]
+    fn renders_a_programmatically_added_command() {
+        let mut maskfile = parse(TEST_MASKFILE.to_string());
+
+        let mut command = Command::new(2);
+        command.name = "lint".to_string();
+        command.description = "Lint the project".to_string();
+        command.scripts.push(Script {
+            executor: "sh".to_string(),
+            source: "cargo clippy".to_string(),
+            ..Script::new()
+        });
+        maskfile.commands.push(command.build());
+
+        let reparsed = parse(maskfile.to_markdown());
+        let lint = reparsed.commands.last().expect("lint command should exist");
+
+        assert_eq!("lint", lint.name);
+        assert_eq!("Lint the project", lint.description);
+        assert_eq!("sh", lint.scripts[0].executor);
+        assert_eq!("cargo clippy\n", lint.scripts[0].source);
+    }
+
+    #[test]
+    fn renders_the_scripts_for_every_os() {
+        let contents = "## build\n\n~~~sh os=linux\necho linux\n~~~\n\n~~~powershell os=windows\nWrite-Output windows\n~~~\n";
+        let maskfile = parse(contents.to_string());
+
+        assert_eq!(
+            "## build\n\n```sh os=linux\necho linux\n```\n\n```powershell\nWrite-Output windows\n```\n",
+            maskfile.to_markdown()
+        );
+    }
+
+    #[test]
+    fn marks_examples_that_could_be_run_as_norun() {
+        let contents = "## build\n\n```json\n{}\n```\n\n```bash norun\nmask build\n```\n\n```bash\necho building\n```\n";
+        let maskfile = parse(contents.to_string());
+
+        assert_eq!(contents, maskfile.to_markdown());
+    }
+
+    #[test]
+    fn renders_script_attributes() {
+        let contents = "## build\n\n```bash {cwd=web msg='say \"hi\"'}\necho building\n```\n";
+        let maskfile = parse(contents.to_string());
+
+        assert_eq!(
+            "## build\n\n```bash {cwd=\"web\", msg='say \"hi\"'}\necho building\n```\n",
+            maskfile.to_markdown()
+        );
+        assert_eq!(
+            maskfile.clone().without_spans(),
+            parse(maskfile.to_markdown()).without_spans()
+        );
+    }
+
+    #[test]
+    fn renders_finally_scripts() {
+        let contents = "## build\n\n```sh\necho building\n```\n\n```sh finally {cwd=\"web\"}\necho cleaning up\n```\n";
+
+        assert_eq!(contents, parse(contents.to_string()).to_markdown());
+    }
+
+    #[test]
+    fn renders_settings() {
+        let contents = "## test\n\n> Run the tests\n\n**SETTINGS**\n* aliases: t, tst\n* hidden: true\n* default: true\n* prompt: true\n* confirm: Run the tests?\n* timeout: 1h 30m\n* retries: 2\n* retry_delay: 5s\n* retry_backoff: true\n* setup: true\n* requires: jq, node>=18\n* sources: src/**/*.rs, Cargo.toml\n* outputs: target/release/app\n* watch: src/**\n* preconditions\n    * `test -f .env`: Copy .env.example to .env first\n    * `docker info`\n\n```sh\necho testing\n```\n";
+
+        assert_eq!(contents, parse(contents.to_string()).to_markdown());
+    }
+
+    #[test]
+    fn uses_a_longer_fence_when_the_source_contains_one() {
+        let block = render_code_block("sh", "cat <<EOF\n```\nEOF\n");
+
+        assert_eq!("````sh\ncat <<EOF\n```\nEOF\n````", block);
+    }
+}
@@ -0,0 +1,428 @@
+use crate::maskfile::*;
+use std::collections::{HashMap, HashSet};
+
+/// Checks the parsed maskfile for mistakes that would make mask fail at runtime.
+pub fn validate(maskfile: &Maskfile) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    validate_commands(&maskfile.commands, &mut diagnostics);
+    diagnostics
+}
+
+fn validate_commands(commands: &[Command], diagnostics: &mut Vec<Diagnostic>) {
+    validate_aliases(commands, diagnostics);
+    validate_default_command(commands, diagnostics);
+
+    let mut names = HashMap::new();
+
+    for c in commands {
+        if names.insert(&c.name, c.span).is_some() {
+            diagnostics.push(Diagnostic::error(
+                c.span,
+                format!("duplicate command `{}`", c.name),
+            ));
+        }
+
+        if c.setup && c.subcommands.is_empty() {
+            diagnostics.push(Diagnostic::warning(
+                c.span,
+                format!(
+                    "command `{}` is a setup for its subcommands but doesn't have any",
+                    c.name
+                ),
+            ));
+        }
+
+        if !c.outputs.is_empty() && c.sources.is_empty() {
+            diagnostics.push(Diagnostic::warning(
+                c.span,
+                format!(
+                    "command `{}` has outputs but no sources to compare them with",
+                    c.name
+                ),
+            ));
+        }
+
+        for script in &c.scripts {
+            if script.executor.is_empty() {
+                diagnostics.push(Diagnostic::error(
+                    script.span,
+                    "code block is missing a lang code which determines which executor to use"
+                        .to_string(),
+                ));
+            }
+
+            if let Some(timeout) = script.attributes.get("timeout") {
+                if humantime::parse_duration(timeout).is_err() {
+                    diagnostics.push(Diagnostic::error(
+                        script.span,
+                        format!(
+                            "attribute `timeout` expects a duration like 10m or 1h 30m, found `{}`",
+                            timeout
+                        ),
+                    ));
+                }
+            }
+        }
+
+        validate_named_flags(c, diagnostics);
+        validate_commands(&c.subcommands, diagnostics);
+    }
+}
+
+/// Aliases can't be the name or alias of a sibling command
+fn validate_aliases(commands: &[Command], diagnostics: &mut Vec<Diagnostic>) {
+    let names: HashSet<&String> = commands.iter().map(|c| &c.name).collect();
+    let mut aliases = HashMap::new();
+
+    for c in commands {
+        for alias in &c.aliases {
+            if names.contains(alias) {
+                diagnostics.push(Diagnostic::error(
+                    c.span,
+                    format!(
+                        "alias `{}` of command `{}` is already the name of a command",
+                        alias, c.name
+                    ),
+                ));
+            } else if let Some(other) = aliases.insert(alias, &c.name) {
+                diagnostics.push(Diagnostic::error(
+                    c.span,
+                    format!(
+                        "alias `{}` of command `{}` is already an alias of `{}`",
+                        alias, c.name, other
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// Only one sibling can be the default and it must be runnable without any args
+fn validate_default_command(commands: &[Command], diagnostics: &mut Vec<Diagnostic>) {
+    let mut default_name = None;
+
+    for c in commands.iter().filter(|c| c.default) {
+        if let Some(name) = default_name {
+            diagnostics.push(Diagnostic::error(
+                c.span,
+                format!(
+                    "command `{}` can't be the default since `{}` already is",
+                    c.name, name
+                ),
+            ));
+        } else {
+            default_name = Some(&c.name);
+        }
+
+        if !c.required_args.is_empty() || c.named_flags.iter().any(|f| f.required) {
+            diagnostics.push(Diagnostic::error(
+                c.span,
+                format!(
+                    "default command `{}` can't have required arguments or flags",
+                    c.name
+                ),
+            ));
+        }
+    }
+}
+
+fn validate_named_flags(cmd: &Command, diagnostics: &mut Vec<Diagnostic>) {
+    let mut names = HashMap::new();
+    let mut shorts = HashMap::new();
+    let mut longs = HashMap::new();
+
+    for flag in &cmd.named_flags {
+        if flag.short.is_empty() && flag.long.is_empty() {
+            diagnostics.push(Diagnostic::warning(
+                flag.span,
+                format!("flag `{}` is missing `flags: -s --long`", flag.name),
+            ));
+        }
+
+        if names.insert(&flag.name, flag.span).is_some() {
+            diagnostics.push(Diagnostic::error(
+                flag.span,
+                format!("duplicate flag `{}`", flag.name),
+            ));
+        }
+
+        if !flag.short.is_empty() {
+            if let Some(other) = shorts.insert(&flag.short, &flag.name) {
+                diagnostics.push(Diagnostic::error(
+                    flag.span,
+                    format!(
+                        "flag `{}` uses `-{}` which is already used by flag `{}`",
+                        flag.name, flag.short, other
+                    ),
+                ));
+            }
+        }
+
+        if !flag.long.is_empty() {
+            if let Some(other) = longs.insert(&flag.long, &flag.name) {
+                diagnostics.push(Diagnostic::error(
+                    flag.span,
+                    format!(
+                        "flag `{}` uses `--{}` which is already used by flag `{}`",
+                        flag.name, flag.long, other
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod validate {
+    use crate::maskfile::*;
+    use crate::parse;
+
+    fn messages(contents: &str) -> Vec<(Severity, usize, String)> {
+        parse(contents.to_string())
+            .diagnostics
+            .into_iter()
+            .map(|d| (d.severity, d.span.line, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn valid_maskfiles_have_no_diagnostics() {
+        let contents = r#"
+## serve
+
+**OPTIONS**
+* port
+    * flags: -p --port
+    * type: number
+    * desc: Which port to serve on
+
+~~~sh
+echo "$port"
+~~~
+"#;
+
+        assert_eq!(Vec::<(Severity, usize, String)>::new(), messages(contents));
+    }
+
+    #[test]
+    fn reports_duplicate_commands() {
+        let contents = "## build\n~~~sh\necho\n~~~\n## build\n~~~sh\necho\n~~~\n";
+
+        assert_eq!(
+            vec![(Severity::Error, 5, "duplicate command `build`".to_string())],
+            messages(contents)
+        );
+    }
+
+    #[test]
+    fn reports_conflicting_aliases() {
+        let contents = r#"## test
+
+**SETTINGS**
+* aliases: t, build
+
+~~~sh
+echo
+~~~
+
+## build
+
+~~~sh
+echo
+~~~
+
+## tidy
+
+**SETTINGS**
+* aliases: t
+
+~~~sh
+echo
+~~~
+"#;
+
+        assert_eq!(
+            vec![
+                (
+                    Severity::Error,
+                    1,
+                    "alias `build` of command `test` is already the name of a command".to_string()
+                ),
+                (
+                    Severity::Error,
+                    16,
+                    "alias `t` of command `tidy` is already an alias of `test`".to_string()
+                ),
+            ],
+            messages(contents)
+        );
+    }
+
+    #[test]
+    fn reports_invalid_default_commands() {
+        let contents = r#"## dev
+
+**SETTINGS**
+* default
+
+~~~sh
+echo
+~~~
+
+## serve (port)
+
+**SETTINGS**
+* default
+
+~~~sh
+echo
+~~~
+"#;
+
+        assert_eq!(
+            vec![
+                (
+                    Severity::Error,
+                    10,
+                    "command `serve` can't be the default since `dev` already is".to_string()
+                ),
+                (
+                    Severity::Error,
+                    10,
+                    "default command `serve` can't have required arguments or flags".to_string()
+                ),
+            ],
+            messages(contents)
+        );
+    }
+
+    #[test]
+    fn reports_code_blocks_without_a_lang_code() {
+        let contents = "## build\n\n~~~\necho\n~~~\n";
+
+        assert_eq!(
+            vec![(
+                Severity::Error,
+                3,
+                "code block is missing a lang code which determines which executor to use"
+                    .to_string()
+            )],
+            messages(contents)
+        );
+    }
+
+    #[test]
+    fn reports_invalid_timeout_attributes() {
+        let contents = "## build\n\n~~~sh {timeout=soon}\necho\n~~~\n";
+
+        assert_eq!(
+            vec![(
+                Severity::Error,
+                3,
+                "attribute `timeout` expects a duration like 10m or 1h 30m, found `soon`"
+                    .to_string()
+            )],
+            messages(contents)
+        );
+    }
+
+    #[test]
+    fn reports_setup_commands_without_subcommands() {
+        let contents = "## docker\n\n**SETTINGS**\n* setup\n\n~~~sh\necho\n~~~\n";
+
+        assert_eq!(
+            vec![(
+                Severity::Warning,
+                1,
+                "command `docker` is a setup for its subcommands but doesn't have any".to_string()
+            )],
+            messages(contents)
+        );
+    }
+
+    #[test]
+    fn reports_outputs_without_sources() {
+        let contents = "## build\n\n**SETTINGS**\n* outputs: dist\n\n~~~sh\necho\n~~~\n";
+
+        assert_eq!(
+            vec![(
+                Severity::Warning,
+                1,
+                "command `build` has outputs but no sources to compare them with".to_string()
+            )],
+            messages(contents)
+        );
+    }
+
+    #[test]
+    fn reports_conflicting_flags() {
+        let contents = r#"## serve
+
+**OPTIONS**
+* values
+    * flags: -v --values
+* port
+    * flags: --port
+* port
+    * flags: --port
+
+~~~sh
+echo
+~~~
+"#;
+
+        assert_eq!(
+            vec![
+                (Severity::Error, 8, "duplicate flag `port`".to_string()),
+                (
+                    Severity::Error,
+                    8,
+                    "flag `port` uses `--port` which is already used by flag `port`".to_string()
+                ),
+                (
+                    Severity::Error,
+                    0,
+                    "flag `verbose` uses `-v` which is already used by flag `values`".to_string()
+                ),
+            ],
+            messages(contents)
+        );
+    }
+
+    #[test]
+    fn reports_unknown_option_config() {
+        let contents = r#"## serve
+
+**OPTIONS**
+* port
+    * flag: --port
+    * type: int
+
+~~~sh
+echo
+~~~
+"#;
+
+        assert_eq!(
+            vec![
+                (
+                    Severity::Warning,
+                    5,
+                    "unknown option `flag`, expected one of: flags, type, desc, choices, required, secret"
+                        .to_string()
+                ),
+                (
+                    Severity::Warning,
+                    6,
+                    "unknown type `int`, expected one of: string, number, boolean".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    4,
+                    "flag `port` is missing `flags: -s --long`".to_string()
+                ),
+            ],
+            messages(contents)
+        );
+    }
+}
@@ -1,43 +1,486 @@
+use crate::requirements;
+use crate::signals;
 use clap::crate_name;
-use mask_parser::maskfile::Command;
+use colored::*;
+use mask_parser::maskfile::{Command, Script};
+use std::ffi::OsStr;
+use std::fs;
 use std::fs::canonicalize;
 use std::io::Result;
 use std::io::{Error, ErrorKind};
 use std::path::{Path, PathBuf};
 use std::process;
-use std::process::ExitStatus;
+use std::process::{Child, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::NamedTempFile;
+use wait_timeout::ChildExt;
 
-pub fn execute_command(cmd: Command, maskfile_path: String) -> Result<ExitStatus> {
-    let script = cmd.script.clone().expect("script should exist");
+/// How long a timed out script has to exit after SIGTERM before it's killed
+const TIMEOUT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Environment variables passed on from one script to the ones after it
+pub type Env = Vec<(String, String)>;
+
+/// Shells whose exported variables can be passed on from setup commands
+const EXPORTING_SHELLS: [&str; 5] = ["sh", "bash", "zsh", "dash", "ksh"];
+
+/// Variables the shell changes on its own, which aren't passed on as exports
+const SHELL_VARIABLES: [&str; 4] = ["PWD", "OLDPWD", "SHLVL", "_"];
+
+/// The shell preconditions are checked with
+#[cfg(not(windows))]
+const PRECONDITION_SHELL: &str = "sh";
+#[cfg(windows)]
+const PRECONDITION_SHELL: &str = "powershell";
+
+/// Runs each of the command's scripts for the current OS in order, stopping at the first one
+/// that fails. Failed commands are run again from the start for as many retries as they have.
+/// Every attempt is stopped with a `TimedOut` error once the shortest of the command's own
+/// timeout and the given one has passed. The `finally` scripts run afterwards no matter how
+/// the others went, with their exit code in `$MASK_EXIT_CODE`.
+///
+/// The setup commands are the command's parents with the `setup` setting, outermost first. Their
+/// scripts run before the command with the variables they export passed on to it, and their
+/// `finally` scripts run after it.
+///
+/// Every script gets the given variables, along with the ones written to `$MASK_ENV` by the
+/// scripts before it. Those are added to the variables for whatever runs next.
+///
+/// Nothing runs unless the programs required by the command and its setups are found. The
+/// preconditions of each of them are checked right before its scripts run.
+pub fn execute_command(
+    cmd: Command,
+    setups: Vec<Command>,
+    maskfile_path: String,
+    timeout: Option<Duration>,
+    env: &mut Env,
+) -> Result<ExitStatus> {
+    for c in setups.iter().chain(std::iter::once(&cmd)) {
+        requirements::check(c)?;
+    }
+    execute_with_setups(&cmd, &setups, &maskfile_path, timeout, env)
+}
+
+fn execute_with_setups(
+    cmd: &Command,
+    setups: &[Command],
+    maskfile_path: &str,
+    timeout: Option<Duration>,
+    env: &mut Env,
+) -> Result<ExitStatus> {
+    let (setup, setups) = match setups.split_first() {
+        Some(first) => first,
+        None => return execute_main_scripts(cmd, maskfile_path, timeout, env),
+    };
+
+    check_preconditions(setup, maskfile_path, env)?;
+    let (finally_scripts, scripts) = partition_scripts(setup);
+    let result = match execute_with_retries(setup, &scripts, maskfile_path, timeout, env) {
+        Ok(status) if status.success() => {
+            execute_with_setups(cmd, setups, maskfile_path, timeout, env)
+        }
+        result => result,
+    };
+    execute_finally_scripts(setup, &finally_scripts, maskfile_path, result, env)
+}
+
+fn execute_main_scripts(
+    cmd: &Command,
+    maskfile_path: &str,
+    timeout: Option<Duration>,
+    env: &mut Env,
+) -> Result<ExitStatus> {
+    let (finally_scripts, scripts) = partition_scripts(cmd);
+    if scripts.is_empty() {
+        let msg = "Command is missing script or lang code which determines which executor to use.";
+        return Err(Error::other(msg));
+    }
+
+    check_preconditions(cmd, maskfile_path, env)?;
+    let result = execute_with_retries(cmd, &scripts, maskfile_path, timeout, env);
+    execute_finally_scripts(cmd, &finally_scripts, maskfile_path, result, env)
+}
+
+/// Fails with the message of the first precondition whose check doesn't exit with 0
+fn check_preconditions(cmd: &Command, maskfile_path: &str, env: &Env) -> Result<()> {
+    for precondition in &cmd.preconditions {
+        let script = Script {
+            executor: PRECONDITION_SHELL.to_string(),
+            source: precondition.check.clone(),
+            ..Script::new()
+        };
+        let mut child = prepare_command(&script);
+        child = add_utility_variables(child, maskfile_path);
+        child.envs(env.iter().map(|(key, val)| (key, val)));
+        child = add_flag_variables(child, cmd);
+
+        // Only the message is shown, not the output of checks like `docker info`
+        let status = child.stdout(Stdio::null()).stderr(Stdio::null()).status()?;
+        if !status.success() {
+            let message = precondition.message.clone().unwrap_or_else(|| {
+                format!(
+                    "`{}` can't run since `{}` failed",
+                    cmd.name, precondition.check
+                )
+            });
+            return Err(Error::other(message));
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits the command's scripts for the current OS into its `finally` scripts and the others
+fn partition_scripts(cmd: &Command) -> (Vec<&Script>, Vec<&Script>) {
+    cmd.scripts_for(std::env::consts::OS)
+        .into_iter()
+        .partition(|script| script.finally)
+}
+
+fn execute_finally_scripts(
+    cmd: &Command,
+    finally_scripts: &[&Script],
+    maskfile_path: &str,
+    result: Result<ExitStatus>,
+    env: &mut Env,
+) -> Result<ExitStatus> {
+    if finally_scripts.is_empty() {
+        return result;
+    }
+
+    let code = match &result {
+        Ok(status) => exit_code(*status),
+        // Same exit code as the timeout command
+        Err(err) if err.kind() == ErrorKind::TimedOut => 124,
+        Err(_) => 1,
+    };
+    env.push(("MASK_EXIT_CODE".to_string(), code.to_string()));
+    let finally_result = execute_scripts(cmd, finally_scripts, maskfile_path, None, env);
+    env.retain(|(key, _)| key != "MASK_EXIT_CODE");
+
+    match result {
+        Ok(status) if status.success() => finally_result,
+        _ => {
+            // The command's own failure is the one reported, so a failing cleanup is only logged
+            if let Err(err) = finally_result {
+                eprintln!("{} {}", "ERROR:".red(), err);
+            }
+            result
+        }
+    }
+}
+
+fn execute_with_retries(
+    cmd: &Command,
+    scripts: &[&Script],
+    maskfile_path: &str,
+    timeout: Option<Duration>,
+    env: &mut Env,
+) -> Result<ExitStatus> {
+    let cmd_timeout = cmd.timeout.as_deref().map(parse_timeout).transpose()?;
+    let mut retry_delay = match &cmd.retry_delay {
+        Some(delay) => parse_duration("retry_delay", delay)?,
+        None => Duration::ZERO,
+    };
+
+    let mut attempt = 1;
+    loop {
+        let deadline = Deadline::earliest(
+            timeout.map(Deadline::after),
+            cmd_timeout.map(Deadline::after),
+        );
+        let status = execute_scripts(cmd, scripts, maskfile_path, deadline, env)?;
+        if status.success() || attempt > cmd.retries {
+            return Ok(status);
+        }
+
+        eprintln!(
+            "{} `{}` failed with {}, retrying in {} (attempt {} of {})",
+            "WARNING:".yellow(),
+            cmd.name,
+            status,
+            humantime::format_duration(retry_delay),
+            attempt + 1,
+            cmd.retries + 1
+        );
+        thread::sleep(retry_delay);
+        if cmd.retry_backoff {
+            retry_delay *= 2;
+        }
+        attempt += 1;
+    }
+}
+
+/// Runs the scripts with the given variables. The variables written to `$MASK_ENV` and the ones
+/// exported by the scripts of setup commands are added to them.
+fn execute_scripts(
+    cmd: &Command,
+    scripts: &[&Script],
+    maskfile_path: &str,
+    deadline: Option<Deadline>,
+    env: &mut Env,
+) -> Result<ExitStatus> {
+    // A command without scripts of its own, like a setup with only finally scripts, succeeds
+    let mut status = ExitStatus::default();
+    for script in scripts {
+        let script_timeout = script
+            .attributes
+            .get("timeout")
+            .map(|t| parse_timeout(t))
+            .transpose()?;
+        let deadline = Deadline::earliest(deadline, script_timeout.map(Deadline::after));
+
+        status = execute_script(cmd, script, maskfile_path, deadline, env)?;
+        if !status.success() {
+            return Ok(status);
+        }
+    }
+
+    Ok(status)
+}
+
+/// Scripts killed by a signal exit with 128 + the signal number, the same as in a shell
+pub fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    1
+}
+
+pub fn parse_timeout(timeout: &str) -> Result<Duration> {
+    parse_duration("timeout", timeout)
+}
+
+fn parse_duration(name: &str, val: &str) -> Result<Duration> {
+    humantime::parse_duration(val).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} `{}` is not a duration like 10m or 1h 30m", name, val),
+        )
+    })
+}
+
+/// When a script has to be done by and the timeout it comes from
+#[derive(Clone, Copy)]
+struct Deadline {
+    at: Instant,
+    timeout: Duration,
+}
+
+impl Deadline {
+    fn after(timeout: Duration) -> Self {
+        Self {
+            at: Instant::now() + timeout,
+            timeout,
+        }
+    }
+
+    fn earliest(a: Option<Deadline>, b: Option<Deadline>) -> Option<Deadline> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(if a.at <= b.at { a } else { b }),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+fn execute_script(
+    cmd: &Command,
+    script: &Script,
+    maskfile_path: &str,
+    deadline: Option<Deadline>,
+    env: &mut Env,
+) -> Result<ExitStatus> {
     if script.source.is_empty() || script.executor.is_empty() {
         let msg = "Command is missing script or lang code which determines which executor to use.";
-        return Err(Error::new(ErrorKind::Other, msg));
+        return Err(Error::other(msg));
     }
 
-    let mut child = prepare_command(&cmd);
+    let exports_file = if passes_on_exports(cmd, script) {
+        Some(temp_file("exports")?)
+    } else {
+        None
+    };
+    let mut child = match &exports_file {
+        Some(file) => {
+            let mut child = prepare_command(&with_exports(script));
+            child.env("MASK_EXPORTS_PATH", file.path());
+            child
+        }
+        None => prepare_command(script),
+    };
     child = add_utility_variables(child, maskfile_path);
-    child = add_flag_variables(child, &cmd);
+    // Scripts pass on variables to the ones after them by appending KEY=value lines to it
+    let env_file = temp_file("env")?;
+    child.env("MASK_ENV", env_file.path());
+    child.envs(env.iter().map(|(key, val)| (key, val)));
+    child = add_script_attributes(child, script, maskfile_path);
+    child = add_flag_variables(child, cmd);
 
-    child
-        .spawn()
-        .map_err(|e| {
-            if e.kind() != ErrorKind::NotFound {
-                return e;
+    // Scripts that can time out get their own process group so the whole group can be stopped
+    let own_group = cfg!(unix) && deadline.is_some();
+    #[cfg(unix)]
+    if own_group {
+        use std::os::unix::process::CommandExt;
+        child.process_group(0);
+    }
+
+    let forwarding = signals::Forwarding::prepare();
+    let mut running = child.spawn().map_err(|e| {
+        if e.kind() != ErrorKind::NotFound {
+            return e;
+        }
+        Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "program '{}' for executor '{}' not in PATH",
+                child.get_program().to_string_lossy(),
+                script.executor
+            ),
+        )
+    })?;
+    forwarding.start(&running, own_group);
+    let status = wait(&mut running, cmd, deadline);
+    drop(forwarding);
+
+    merge_env(env, read_env_file(env_file.path())?);
+    if let Some(file) = exports_file {
+        if matches!(status, Ok(status) if status.success()) {
+            // The file stays empty when the script replaced the trap that writes to it
+            if fs::metadata(file.path())?.len() == 0 {
+                eprintln!(
+                    "{} `{}` set its own EXIT trap, so the variables it exports aren't passed on",
+                    "WARNING:".yellow(),
+                    cmd.name
+                );
+            } else {
+                merge_env(env, read_exports(file.path(), &child)?);
             }
-            Error::new(
-                ErrorKind::NotFound,
-                format!(
-                    "program '{}' for executor '{}' not in PATH",
-                    child.get_program().to_string_lossy(),
-                    script.executor
-                ),
-            )
-        })?
-        .wait()
+        }
+    }
+
+    status
+}
+
+/// An empty file in the temp dir for a script to write to, which is removed once it's dropped.
+/// It gets a random name and only the current user can read it.
+fn temp_file(name: &str) -> Result<NamedTempFile> {
+    tempfile::Builder::new()
+        .prefix(&format!("mask-{}-", name))
+        .tempfile()
+}
+
+/// Adds the variables, replacing the ones with the same name
+fn merge_env(env: &mut Env, vars: Env) {
+    for (key, val) in vars {
+        env.retain(|(k, _)| k != &key);
+        env.push((key, val));
+    }
 }
 
-fn prepare_command(cmd: &Command) -> process::Command {
-    let script = cmd.script.clone().expect("script should exist");
+/// The KEY=value lines written to `$MASK_ENV`. Other lines are ignored.
+fn read_env_file(path: &Path) -> Result<Env> {
+    let contents = fs::read_to_string(path)?;
+    let vars = contents
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, val)| (key.to_string(), val.to_string()))
+        .collect();
+    Ok(vars)
+}
+
+/// Waits for the script to exit, stopping it once the deadline has passed
+fn wait(running: &mut Child, cmd: &Command, deadline: Option<Deadline>) -> Result<ExitStatus> {
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => return running.wait(),
+    };
+    let remaining = deadline.at.saturating_duration_since(Instant::now());
+    if let Some(status) = running.wait_timeout(remaining)? {
+        return Ok(status);
+    }
+
+    terminate(running)?;
+    Err(Error::new(
+        ErrorKind::TimedOut,
+        format!(
+            "`{}` timed out after {}",
+            cmd.name,
+            humantime::format_duration(deadline.timeout)
+        ),
+    ))
+}
+
+/// Only the variables exported by the shell scripts of setup commands are passed on
+fn passes_on_exports(cmd: &Command, script: &Script) -> bool {
+    cmd.setup && !script.finally && EXPORTING_SHELLS.contains(&script.executor.as_str())
+}
+
+/// Makes the shell write its environment to `$MASK_EXPORTS_PATH` when it exits, however it exits.
+/// Scripts that set their own EXIT trap replace this one.
+fn with_exports(script: &Script) -> Script {
+    let mut script = script.clone();
+    script.source = format!(
+        "trap 'env -0 > \"$MASK_EXPORTS_PATH\"' EXIT\n{}",
+        script.source
+    );
+    script
+}
+
+/// The variables the script exported, which are the ones that changed from what it was given
+fn read_exports(path: &Path, child: &process::Command) -> Result<Env> {
+    let contents = fs::read(path)?;
+    let exports = contents
+        .split(|byte| *byte == 0)
+        .filter_map(|var| {
+            String::from_utf8_lossy(var)
+                .split_once('=')
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+        })
+        .filter(|(key, _)| !SHELL_VARIABLES.contains(&key.as_str()))
+        .filter(|(key, val)| {
+            let given = match child.get_envs().find(|(k, _)| *k == key.as_str()) {
+                Some((_, given)) => given.map(OsStr::to_os_string),
+                None => std::env::var_os(key),
+            };
+            given.as_deref() != Some(OsStr::new(val))
+        })
+        .collect();
+    Ok(exports)
+}
+
+/// Asks the script's process group to stop and kills it if it's still running after a grace period
+#[cfg(unix)]
+fn terminate(child: &mut Child) -> Result<()> {
+    let group = -(child.id() as libc::pid_t);
+    unsafe { libc::kill(group, libc::SIGTERM) };
+    let exited = child.wait_timeout(TIMEOUT_GRACE_PERIOD)?.is_some();
+    // Also kills anything in the group that outlived the script
+    unsafe { libc::kill(group, libc::SIGKILL) };
+    if !exited {
+        child.wait()?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn terminate(child: &mut Child) -> Result<()> {
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+fn prepare_command(script: &Script) -> process::Command {
     let executor = script.executor.clone();
     let source = script.source.clone();
 
@@ -85,7 +528,7 @@ fn prepare_command(cmd: &Command) -> process::Command {
 }
 
 // Add some useful environment variables that scripts can use
-fn add_utility_variables(mut child: process::Command, maskfile_path: String) -> process::Command {
+fn add_utility_variables(mut child: process::Command, maskfile_path: &str) -> process::Command {
     let maskfile_path = PathBuf::from(maskfile_path);
 
     // Find the absolute path to the maskfile
@@ -114,6 +557,33 @@ fn add_utility_variables(mut child: process::Command, maskfile_path: String) ->
     child
 }
 
+// Apply the settings from the code block's attributes like {cwd="web", env="KEY=value"}
+fn add_script_attributes(
+    mut child: process::Command,
+    script: &Script,
+    maskfile_path: &str,
+) -> process::Command {
+    // Relative to the maskfile so it doesn't matter where mask is called from
+    if let Some(cwd) = script.attributes.get("cwd") {
+        let maskfile_dir = Path::new(maskfile_path).parent().unwrap_or(Path::new(""));
+        child.current_dir(maskfile_dir.join(cwd));
+    }
+
+    // Multiple variables are separated by commas or whitespace, like `A=1, B=2` or `A=1 B=2`
+    if let Some(env) = script.attributes.get("env") {
+        for var in env
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|var| !var.is_empty())
+        {
+            if let Some((key, val)) = var.split_once('=') {
+                child.env(key, val);
+            }
+        }
+    }
+
+    child
+}
+
 fn add_flag_variables(mut child: process::Command, cmd: &Command) -> process::Command {
     // Add all required args as environment variables
     for arg in &cmd.required_args {
@@ -127,7 +597,7 @@ fn add_flag_variables(mut child: process::Command, cmd: &Command) -> process::Co
 
     // Add all named flags as environment variables if they have a value
     for flag in &cmd.named_flags {
-        if flag.val != "" {
+        if !flag.val.is_empty() {
             child.env(flag.name.clone(), flag.val.clone());
         }
     }
@@ -0,0 +1,62 @@
+use clap::{App, AppSettings, Arg, SubCommand};
+use colored::*;
+use similar::TextDiff;
+use std::fs;
+use std::io::Result;
+
+pub const NAME: &str = "fmt";
+
+/// Built-in subcommand for formatting the maskfile
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Format the maskfile")
+        .setting(AppSettings::ColoredHelp)
+        .arg(
+            Arg::with_name("check")
+                .help("Exit with an error and print a diff if the maskfile isn't formatted")
+                .long("check"),
+        )
+}
+
+/// Formats the maskfile in place. In check mode, the maskfile is left untouched and
+/// false is returned if it isn't formatted.
+pub fn format_maskfile(
+    maskfile_contents: String,
+    maskfile_path: &str,
+    check: bool,
+) -> Result<bool> {
+    let formatted = mask_parser::format(maskfile_contents.clone());
+    if formatted == maskfile_contents {
+        return Ok(true);
+    }
+
+    if check {
+        print_diff(&maskfile_contents, &formatted, maskfile_path);
+        return Ok(false);
+    }
+
+    fs::write(maskfile_path, formatted)?;
+    Ok(true)
+}
+
+fn print_diff(original: &str, formatted: &str, maskfile_path: &str) {
+    let diff = TextDiff::from_lines(original, formatted);
+    let unified_diff = diff
+        .unified_diff()
+        .header(maskfile_path, maskfile_path)
+        .to_string();
+
+    for line in unified_diff.lines() {
+        if line.starts_with("+++") || line.starts_with("---") {
+            println!("{}", line.bold());
+        } else if line.starts_with('+') {
+            println!("{}", line.green());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use clap::{App, AppSettings, Arg, SubCommand};
+use mask_parser::maskfile::{Command, Span};
+use std::path::Path;
+
+pub const NAME: &str = "where";
+
+/// Built-in subcommand for finding where a command is defined
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Print the file and line where a command is defined")
+        .setting(AppSettings::ColoredHelp)
+        .arg(
+            Arg::with_name("command")
+                .help("The command to find, including any parent commands")
+                .required(true)
+                .multiple(true),
+        )
+}
+
+/// Finds the command by walking down the tree of subcommands
+pub fn find_command_by_names<'a>(commands: &'a [Command], names: &[&str]) -> Option<&'a Command> {
+    let (name, rest) = names.split_first()?;
+    // A command's name wins over the same alias of another one
+    let cmd = commands
+        .iter()
+        .find(|c| c.name == *name)
+        .or_else(|| commands.iter().find(|c| c.has_name(name)))?;
+    if rest.is_empty() {
+        Some(cmd)
+    } else {
+        find_command_by_names(&cmd.subcommands, rest)
+    }
+}
+
+/// Formats a span as `path:line` which most editors and terminals can jump to
+pub fn location(maskfile_path: &str, span: &Span) -> String {
+    let path = Path::new(maskfile_path);
+    let path = path.strip_prefix("./").unwrap_or(path);
+    format!("{}:{}", path.display(), span.line)
+}
@@ -0,0 +1,505 @@
+use clap::{crate_name, App, AppSettings, SubCommand};
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as _,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, SymbolKind, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+use mask_parser::maskfile::{Command, Maskfile, Severity, Span};
+use mask_parser::ParseOptions;
+use std::collections::HashMap;
+use std::error::Error;
+
+pub const NAME: &str = "lsp";
+
+/// Built-in subcommand for starting the language server
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Start a language server for maskfiles over stdio")
+        .setting(AppSettings::ColoredHelp)
+}
+
+/// Keys that can be used within an OPTIONS list and what they do
+const OPTION_CONFIG_COMPLETIONS: [(&str, &str); 6] = [
+    ("desc", "Description of the flag shown in the help output"),
+    ("type", "Type of the flag value: string, number or boolean"),
+    ("flags", "Short and long names of the flag, e.g. -p --port"),
+    ("choices", "Comma separated list of allowed values"),
+    ("required", "Make the flag required"),
+    ("secret", "Hide the value while it's typed into a prompt"),
+];
+
+const TYPE_COMPLETIONS: [(&str, &str); 3] = [
+    ("string", "The flag takes a value"),
+    (
+        "number",
+        "The flag takes a value which is validated as a number",
+    ),
+    ("boolean", "The flag doesn't take a value"),
+];
+
+pub fn run_server() -> Result<(), Box<dyn Error + Sync + Send>> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![" ".to_string(), ":".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    // The contents of every open document
+    let mut documents: HashMap<Url, String> = HashMap::new();
+
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    break;
+                }
+                let response = handle_request(&documents, req);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(uri) = handle_notification(&mut documents, notification)? {
+                    let diagnostics = diagnostics(&uri, documents.get(&uri));
+                    connection.sender.send(Message::Notification(diagnostics))?;
+                }
+            }
+            Message::Response(_) => (),
+        }
+    }
+
+    // The writer thread only stops once the connection is dropped
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Updates the open documents. Returns the uri of the document that changed.
+fn handle_notification(
+    documents: &mut HashMap<Url, String>,
+    notification: Notification,
+) -> Result<Option<Url>, serde_json::Error> {
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: DidOpenTextDocumentParams = serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            documents.insert(uri.clone(), params.text_document.text);
+            Ok(Some(uri))
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: DidChangeTextDocumentParams = serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            // Full sync is used so the last change contains the whole document
+            if let Some(change) = params.content_changes.into_iter().last() {
+                documents.insert(uri.clone(), change.text);
+            }
+            Ok(Some(uri))
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: DidCloseTextDocumentParams = serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            documents.remove(&uri);
+            Ok(Some(uri))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn handle_request(documents: &HashMap<Url, String>, req: Request) -> Response {
+    let result = match req.method.as_str() {
+        HoverRequest::METHOD => handle(req.params, |params: HoverParams| {
+            let position = params.text_document_position_params;
+            with_document(documents, &position.text_document.uri, |text| {
+                hover(text, position.position)
+            })
+        }),
+        Completion::METHOD => handle(req.params, |params: CompletionParams| {
+            let position = params.text_document_position;
+            with_document(documents, &position.text_document.uri, |text| {
+                completion(text, position.position)
+            })
+        }),
+        DocumentSymbolRequest::METHOD => handle(req.params, |params: DocumentSymbolParams| {
+            with_document(documents, &params.text_document.uri, |text| {
+                Some(DocumentSymbolResponse::Nested(document_symbols(text)))
+            })
+        }),
+        GotoDefinition::METHOD => handle(req.params, |params: GotoDefinitionParams| {
+            let uri = params.text_document_position_params.text_document.uri;
+            let position = params.text_document_position_params.position;
+            with_document(documents, &uri, |text| {
+                definition(text, position).map(|range| {
+                    GotoDefinitionResponse::Scalar(Location {
+                        uri: uri.clone(),
+                        range,
+                    })
+                })
+            })
+        }),
+        _ => {
+            return Response::new_err(
+                req.id,
+                lsp_server::ErrorCode::MethodNotFound as i32,
+                format!("unsupported method {}", req.method),
+            )
+        }
+    };
+
+    match result {
+        Ok(value) => Response::new_ok(req.id, value),
+        Err(err) => invalid_params(req.id, err),
+    }
+}
+
+fn handle<P, R>(
+    params: serde_json::Value,
+    handler: impl FnOnce(P) -> Option<R>,
+) -> Result<serde_json::Value, serde_json::Error>
+where
+    P: serde::de::DeserializeOwned,
+    R: serde::Serialize,
+{
+    let params = serde_json::from_value(params)?;
+    serde_json::to_value(handler(params))
+}
+
+fn with_document<R>(
+    documents: &HashMap<Url, String>,
+    uri: &Url,
+    f: impl FnOnce(&str) -> Option<R>,
+) -> Option<R> {
+    documents.get(uri).and_then(|text| f(text))
+}
+
+fn invalid_params(id: RequestId, err: serde_json::Error) -> Response {
+    Response::new_err(
+        id,
+        lsp_server::ErrorCode::InvalidParams as i32,
+        err.to_string(),
+    )
+}
+
+/// Editors show the commands for every OS, not only the ones that can run here
+fn parse(text: &str) -> Maskfile {
+    mask_parser::parse_with_options(text.to_string(), &ParseOptions { target_os: None })
+}
+
+fn diagnostics(uri: &Url, text: Option<&String>) -> Notification {
+    // Closed documents have their diagnostics cleared
+    let diagnostics = match text {
+        Some(text) => parse(text)
+            .diagnostics
+            .into_iter()
+            .map(|d| lsp_types::Diagnostic {
+                range: range(text, &d.span),
+                severity: Some(match d.severity {
+                    Severity::Error => DiagnosticSeverity::ERROR,
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                }),
+                source: Some(crate_name!().to_string()),
+                message: d.message,
+                ..lsp_types::Diagnostic::default()
+            })
+            .collect(),
+        None => vec![],
+    };
+
+    Notification::new(
+        PublishDiagnostics::METHOD.to_string(),
+        PublishDiagnosticsParams {
+            uri: uri.clone(),
+            diagnostics,
+            version: None,
+        },
+    )
+}
+
+/// Shows the generated help output when hovering over a command's heading
+fn hover(text: &str, position: Position) -> Option<Hover> {
+    let maskfile = parse(text);
+    let names = command_path_at_line(&maskfile.commands, position.line as usize + 1)?;
+
+    let mut args = vec![crate_name!().to_string()];
+    args.extend(names);
+    args.push("--help".to_string());
+
+    // clap returns the help output as an error instead of printing it
+    let examples = crate::examples_help(&maskfile.commands);
+    let help =
+        crate::build_subcommands(crate::build_cli_app(), &maskfile.commands, &examples, false)
+            .get_matches_from_safe(args)
+            .err()
+            .filter(|err| err.kind == clap::ErrorKind::HelpDisplayed)?
+            .message;
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("```text\n{}\n```", help.trim_end()),
+        }),
+        range: None,
+    })
+}
+
+/// Finds the names of the command (and its parents) whose heading is on the given line
+fn command_path_at_line(commands: &[Command], line: usize) -> Option<Vec<String>> {
+    for c in commands {
+        if c.span.line == line {
+            return Some(vec![c.name.clone()]);
+        }
+        if let Some(mut names) = command_path_at_line(&c.subcommands, line) {
+            names.insert(0, c.name.clone());
+            return Some(names);
+        }
+    }
+    None
+}
+
+/// Completes the config keys and type values within an OPTIONS list
+fn completion(text: &str, position: Position) -> Option<CompletionResponse> {
+    let lines: Vec<&str> = text.lines().collect();
+    let line_idx = position.line as usize;
+    let line = lines.get(line_idx)?;
+    let prefix = &line[..byte_offset_in_line(line, position.character)];
+
+    if !is_in_options_list(&lines[..line_idx]) {
+        return None;
+    }
+
+    // Nested items of the OPTIONS list are the flag config
+    let trimmed = prefix.trim_start();
+    if prefix.len() - trimmed.len() < 2 || !trimmed.starts_with(['*', '-', '+']) {
+        return None;
+    }
+    let config = trimmed[1..].trim_start();
+
+    let (completions, kind) = if config.starts_with("type:") {
+        (&TYPE_COMPLETIONS[..], CompletionItemKind::VALUE)
+    } else if !config.contains(':') {
+        (&OPTION_CONFIG_COMPLETIONS[..], CompletionItemKind::PROPERTY)
+    } else {
+        return None;
+    };
+
+    let items = completions
+        .iter()
+        .map(|(label, detail)| CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            detail: Some(detail.to_string()),
+            insert_text: Some(match (kind, *label) {
+                (CompletionItemKind::PROPERTY, "required" | "secret") => label.to_string(),
+                (CompletionItemKind::PROPERTY, _) => format!("{}: ", label),
+                _ => label.to_string(),
+            }),
+            ..CompletionItem::default()
+        })
+        .collect();
+
+    Some(CompletionResponse::Array(items))
+}
+
+/// Checks if the list that the preceding lines belong to is an OPTIONS list
+fn is_in_options_list(preceding_lines: &[&str]) -> bool {
+    for line in preceding_lines.iter().rev() {
+        let trimmed = line.trim();
+        if matches!(trimmed, "**OPTIONS**" | "__OPTIONS__" | "OPTIONS") {
+            return true;
+        }
+        if !trimmed.starts_with(['*', '-', '+']) {
+            return false;
+        }
+    }
+    false
+}
+
+/// Provides the command tree as an outline of the maskfile
+fn document_symbols(text: &str) -> Vec<DocumentSymbol> {
+    let maskfile = parse(text);
+
+    // The start of every heading in document order, used to find where each section ends
+    let mut headings = vec![];
+    collect_headings(&maskfile.commands, &mut headings);
+
+    symbols(text, &maskfile.commands, &headings)
+}
+
+fn collect_headings(commands: &[Command], headings: &mut Vec<(u8, usize)>) {
+    for c in commands {
+        headings.push((c.level, c.span.start));
+        collect_headings(&c.subcommands, headings);
+    }
+}
+
+#[allow(deprecated)]
+fn symbols(text: &str, commands: &[Command], headings: &[(u8, usize)]) -> Vec<DocumentSymbol> {
+    commands
+        .iter()
+        .map(|c| {
+            let section_end = headings
+                .iter()
+                .find(|(level, start)| *start > c.span.start && *level <= c.level)
+                .map(|(_, start)| *start)
+                .unwrap_or(text.len());
+            let section = Span {
+                start: c.span.start,
+                end: section_end,
+                line: c.span.line,
+            };
+
+            DocumentSymbol {
+                name: c.name.clone(),
+                detail: Some(c.description.clone()).filter(|d| !d.is_empty()),
+                kind: SymbolKind::FUNCTION,
+                tags: None,
+                deprecated: None,
+                range: range(text, &section),
+                selection_range: range(text, &c.span),
+                children: Some(symbols(text, &c.subcommands, headings)),
+            }
+        })
+        .collect()
+}
+
+/// Jumps from a `$MASK <command>` reference within a script to the command's heading
+fn definition(text: &str, position: Position) -> Option<Range> {
+    let maskfile = parse(text);
+    let offset = offset(text, position)?;
+
+    if !is_within_script(&maskfile.commands, offset) {
+        return None;
+    }
+
+    let line_start = text[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = text[offset..]
+        .find('\n')
+        .map(|i| offset + i)
+        .unwrap_or(text.len());
+    let line = &text[line_start..line_end];
+
+    for reference in ["$MASK", "${MASK}"] {
+        for (idx, _) in line.match_indices(reference) {
+            let args_start = idx + reference.len();
+            let args: String = line[args_start..]
+                .chars()
+                .take_while(|c| !matches!(c, ';' | '|' | '&' | ')' | '`' | '#' | '\n'))
+                .collect();
+            let reference_end = line_start + args_start + args.len();
+            if offset < line_start + idx || offset > reference_end {
+                continue;
+            }
+
+            let names: Vec<&str> = args.split_whitespace().collect();
+            let cmd = find_longest_command_path(&maskfile.commands, &names)?;
+            return Some(range(text, &cmd.span));
+        }
+    }
+
+    None
+}
+
+fn find_longest_command_path<'a>(commands: &'a [Command], names: &[&str]) -> Option<&'a Command> {
+    let (name, rest) = names.split_first()?;
+    // A command's name wins over the same alias of another one
+    let cmd = commands
+        .iter()
+        .find(|c| c.name == *name)
+        .or_else(|| commands.iter().find(|c| c.has_name(name)))?;
+    find_longest_command_path(&cmd.subcommands, rest).or(Some(cmd))
+}
+
+fn is_within_script(commands: &[Command], offset: usize) -> bool {
+    commands.iter().any(|c| {
+        let in_script = c
+            .scripts
+            .iter()
+            .any(|s| s.span.start <= offset && offset < s.span.end);
+        in_script || is_within_script(&c.subcommands, offset)
+    })
+}
+
+fn range(text: &str, span: &Span) -> Range {
+    // Exclude trailing blank lines so the range ends where the content does
+    let end = std::cmp::min(span.end, text.len());
+    let end = span.start + text[span.start..end].trim_end().len();
+    Range::new(position(text, span.start), position(text, end))
+}
+
+/// Converts a byte offset into an LSP position which counts characters in UTF-16
+fn position(text: &str, offset: usize) -> Position {
+    let offset = std::cmp::min(offset, text.len());
+    let line_start = text[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line = text[..offset].matches('\n').count();
+    let character: usize = text[line_start..offset].chars().map(char::len_utf16).sum();
+    Position::new(line as u32, character as u32)
+}
+
+/// Converts an LSP position into a byte offset
+fn offset(text: &str, position: Position) -> Option<usize> {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        line_start += text[line_start..].find('\n')? + 1;
+    }
+    let line = text[line_start..].lines().next().unwrap_or("");
+    Some(line_start + byte_offset_in_line(line, position.character))
+}
+
+fn byte_offset_in_line(line: &str, character: u32) -> usize {
+    let mut utf16_count = 0;
+    for (idx, c) in line.char_indices() {
+        if utf16_count >= character as usize {
+            return idx;
+        }
+        utf16_count += c.len_utf16();
+    }
+    line.len()
+}
+
+#[cfg(test)]
+mod positions {
+    use super::*;
+
+    #[test]
+    fn converts_between_offsets_and_positions() {
+        let text = "# Tasks\n\n## héllo\n~~~sh\necho\n~~~\n";
+
+        assert_eq!(Position::new(2, 4), position(text, 13));
+        assert_eq!(Some(13), offset(text, Position::new(2, 4)));
+        // Multi-byte characters count as one UTF-16 unit
+        assert_eq!(Position::new(2, 5), position(text, 15));
+        assert_eq!(Some(15), offset(text, Position::new(2, 5)));
+    }
+}
+
+#[cfg(test)]
+mod options_list {
+    use super::*;
+
+    #[test]
+    fn detects_config_items_of_an_options_list() {
+        let lines = ["## serve", "", "**OPTIONS**", "* port", "    * flags: -p"];
+
+        assert!(is_in_options_list(&lines));
+        assert!(!is_in_options_list(&lines[..2]));
+        assert!(!is_in_options_list(&["## serve", "* not options"]));
+    }
+}
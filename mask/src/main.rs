@@ -1,21 +1,34 @@
 mod executor;
+mod fmt;
 mod loader;
+mod locate;
+mod lsp;
+mod prompt;
+mod requirements;
+mod signals;
+mod up_to_date;
+mod watch;
 use clap::{crate_name, crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
 use colored::*;
-use executor::execute_command;
-use mask_parser::maskfile::Command;
+use executor::{execute_command, exit_code};
+use mask_parser::maskfile::{Command, Example, Severity};
+use mask_parser::ParseOptions;
 use std::env;
+use std::io::ErrorKind;
 use std::path::Path;
+use std::time::Duration;
 
 fn main() {
-    let cli_app = App::new(crate_name!())
-        .setting(AppSettings::VersionlessSubcommands)
-        .setting(AppSettings::AllowNegativeNumbers)
-        .setting(AppSettings::SubcommandRequired)
-        .setting(AppSettings::ColoredHelp)
-        .version(crate_version!())
-        .arg(custom_maskfile_path_arg())
-        .arg(introspect_arg());
+    // The language server talks over stdout, so it must start before anything else is printed
+    if is_starting_language_server() {
+        if let Err(err) = lsp::run_server() {
+            eprintln!("{} {}", "ERROR:".red(), err);
+            std::process::exit(1)
+        }
+        return;
+    }
+
+    let cli_app = build_cli_app();
 
     let (maskfile, maskfile_path) = find_maskfile();
     if maskfile.is_err() {
@@ -24,7 +37,13 @@ fn main() {
         return;
     }
 
-    let root = mask_parser::parse(maskfile.unwrap());
+    let maskfile_contents = maskfile.unwrap();
+    // Every command is parsed so --introspect and `where` can see them, and the ones for another
+    // OS are left out of the CLI below
+    let root = mask_parser::parse_with_options(
+        maskfile_contents.clone(),
+        &ParseOptions { target_os: None },
+    );
 
     if is_introspecting() {
         let json = root.to_json().expect("to_json failed");
@@ -32,20 +51,273 @@ fn main() {
         return;
     }
 
-    let matches = build_subcommands(cli_app, &root.commands).get_matches();
-    let chosen_cmd =
-        find_command(&matches, &root.commands).expect("SubcommandRequired failed to work");
+    // Commands whose code blocks are all for another OS can't be run here
+    let commands = commands_for_current_os(&root.commands);
+
+    // Built-in subcommands can be overridden by commands of the same name in the maskfile
+    let is_builtin = |name: &str| !commands.iter().any(|c| c.has_name(name));
+
+    let invocation_args = split_invocations(env::args().collect());
+    let prompting = is_prompting(&invocation_args);
+    let examples = examples_help(&commands);
+    let mut cli_app = build_subcommands(cli_app, &commands, &examples, prompting);
+    // Running without a command picks one instead of failing when there's a default or a terminal
+    if default_command(&commands).is_some() || prompt::is_interactive() {
+        cli_app = cli_app.unset_setting(AppSettings::SubcommandRequired);
+    }
+    if is_builtin(fmt::NAME) {
+        cli_app = cli_app.subcommand(fmt::subcommand());
+    }
+    if is_builtin(locate::NAME) {
+        cli_app = cli_app.subcommand(locate::subcommand());
+    }
+    if is_builtin(lsp::NAME) {
+        cli_app = cli_app.subcommand(lsp::subcommand());
+    }
+
+    // Every invocation is resolved before any of them runs, so a typo in the last one doesn't
+    // cost a run of the ones before it
+    let mut invocations = vec![];
+    let mut timeout = None;
+    let mut force = false;
+    let mut assume_yes = false;
+    let mut watching = false;
+    for args in invocation_args {
+        let matches = cli_app.clone().get_matches_from(args);
 
-    match execute_command(chosen_cmd, maskfile_path) {
-        Ok(status) => match status.code() {
-            Some(code) => std::process::exit(code),
-            None => return,
-        },
-        Err(err) => {
+        // A timeout, --force or --yes given for a command applies to the ones after it too
+        force = force || matches.is_present("force");
+        assume_yes = assume_yes || find_option(&matches, None, &commands, "yes").is_some();
+        watching = watching || matches.is_present("watch");
+        if let Some(val) =
+            find_option(&matches, None, &commands, "timeout").and_then(|m| m.value_of("timeout"))
+        {
+            match executor::parse_timeout(val) {
+                Ok(duration) => timeout = Some(duration),
+                Err(err) => {
+                    eprintln!("{} {}", "ERROR:".red(), err);
+                    std::process::exit(1)
+                }
+            }
+        }
+
+        let action = match matches.subcommand() {
+            (fmt::NAME, Some(fmt_matches)) if is_builtin(fmt::NAME) => Action::Fmt {
+                check: fmt_matches.is_present("check"),
+            },
+            (locate::NAME, Some(where_matches)) if is_builtin(locate::NAME) => {
+                let names: Vec<&str> = where_matches.values_of("command").unwrap().collect();
+                match locate::find_command_by_names(&root.commands, &names) {
+                    Some(cmd) => Action::Where(locate::location(&maskfile_path, &cmd.span)),
+                    None => {
+                        eprintln!("{} command `{}` not found", "ERROR:".red(), names.join(" "));
+                        std::process::exit(1)
+                    }
+                }
+            }
+            _ => match find_command(&matches, &commands, &maskfile_path)
+                .or_else(|| find_default_command(&matches, &commands, &maskfile_path))
+            {
+                Some(mut cmd) => {
+                    if prompts_for_missing_values(&cmd, prompting) {
+                        if let Err(err) = prompt::prompt_missing_values(&mut cmd) {
+                            eprintln!("{} {}", "ERROR:".red(), err);
+                            std::process::exit(1)
+                        }
+                    }
+                    Action::Run(Box::new(cmd))
+                }
+                None => match prompt::pick_command(&commands) {
+                    Ok(Some(cmd)) => Action::Run(Box::new(cmd)),
+                    // The user backed out of the picker
+                    Ok(None) => return,
+                    Err(err) => {
+                        eprintln!("{} {}", "ERROR:".red(), err);
+                        std::process::exit(1)
+                    }
+                },
+            },
+        };
+        invocations.push(Invocation {
+            action,
+            timeout,
+            force,
+            assume_yes,
+        });
+    }
+
+    // Mistakes like conflicting aliases are pointed out before anything runs, though only the
+    // ones in the sections of the commands that are about to run or their parents
+    for diagnostic in &root.diagnostics {
+        let is_affecting = |cmd: &Command| match command_at(&root.commands, diagnostic.span.start) {
+            Some(owner) => contains_command(owner, cmd),
+            None => true,
+        };
+        let is_running = invocations
+            .iter()
+            .any(|invocation| match &invocation.action {
+                Action::Run(cmd) => is_affecting(cmd),
+                _ => false,
+            });
+        if diagnostic.severity == Severity::Error && is_running {
+            eprintln!(
+                "{} {}: {}",
+                "ERROR:".red(),
+                locate::location(&maskfile_path, &diagnostic.span),
+                diagnostic.message
+            );
+        }
+    }
+
+    // The commands are run by another mask again whenever their files change
+    if watching {
+        let watched: Vec<Command> = invocations
+            .into_iter()
+            .filter_map(|invocation| match invocation.action {
+                Action::Run(cmd) => Some(*cmd),
+                _ => None,
+            })
+            .collect();
+        if let Err(err) = watch::watch(&watched, &maskfile_path) {
             eprintln!("{} {}", "ERROR:".red(), err);
             std::process::exit(1)
         }
+        return;
     }
+
+    // Every invocation runs in order until one of them fails
+    // Variables written to $MASK_ENV are passed on to the commands after it
+    let mut env = vec![];
+    for invocation in invocations {
+        let chosen_cmd = match invocation.action {
+            Action::Fmt { check } => {
+                match fmt::format_maskfile(maskfile_contents.clone(), &maskfile_path, check) {
+                    Ok(true) => continue,
+                    Ok(false) => {
+                        eprintln!("{} maskfile is not formatted", "ERROR:".red());
+                        std::process::exit(1)
+                    }
+                    Err(err) => {
+                        eprintln!("{} {}", "ERROR:".red(), err);
+                        std::process::exit(1)
+                    }
+                }
+            }
+            Action::Where(location) => {
+                println!("{}", location);
+                continue;
+            }
+            Action::Run(cmd) => *cmd,
+        };
+
+        let path = command_path(&commands, &chosen_cmd);
+        if !invocation.force {
+            match up_to_date::is_up_to_date(&chosen_cmd, &path, &maskfile_path) {
+                Ok(true) => {
+                    println!("`{}` is up to date", chosen_cmd.name);
+                    continue;
+                }
+                Ok(false) => (),
+                Err(err) => {
+                    eprintln!("{} {}", "ERROR:".red(), err);
+                    std::process::exit(1)
+                }
+            }
+        }
+
+        if let Some(message) = &chosen_cmd.confirm {
+            if !invocation.assume_yes && !is_assuming_yes_by_env() {
+                match prompt::confirm(&chosen_cmd, message) {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        eprintln!(
+                            "{} `{}` was not confirmed. Use --yes to run it without a prompt.",
+                            "ERROR:".red(),
+                            chosen_cmd.name
+                        );
+                        std::process::exit(1)
+                    }
+                    Err(err) => {
+                        eprintln!("{} {}", "ERROR:".red(), err);
+                        std::process::exit(1)
+                    }
+                }
+            }
+        }
+
+        let setups = find_setup_commands(&commands, &chosen_cmd);
+        match execute_command(
+            chosen_cmd.clone(),
+            setups,
+            maskfile_path.clone(),
+            invocation.timeout,
+            &mut env,
+        ) {
+            Ok(status) => {
+                let code = exit_code(status);
+                if code != 0 {
+                    std::process::exit(code)
+                }
+                if let Err(err) = up_to_date::save_fingerprint(&chosen_cmd, &path, &maskfile_path) {
+                    eprintln!("{} {}", "WARNING:".yellow(), err);
+                }
+            }
+            Err(err) => {
+                eprintln!("{} {}", "ERROR:".red(), err);
+                // Same exit code as the timeout command
+                if err.kind() == ErrorKind::TimedOut {
+                    std::process::exit(124)
+                }
+                std::process::exit(1)
+            }
+        }
+    }
+}
+
+/// One command from the args, like `mask fmt , build`
+struct Invocation {
+    action: Action,
+    timeout: Option<Duration>,
+    force: bool,
+    assume_yes: bool,
+}
+
+enum Action {
+    Fmt {
+        check: bool,
+    },
+    /// Prints where a command is defined
+    Where(String),
+    Run(Box<Command>),
+}
+
+/// Splits the args into one invocation per command, like `mask build --release , test unit`.
+/// The options before the first command like --maskfile only need to be given once.
+fn split_invocations(args: Vec<String>) -> Vec<Vec<String>> {
+    let mut groups = args.split(|a| a == ",");
+    let mut invocations = vec![groups.next().unwrap_or_default().to_vec()];
+    for group in groups {
+        let mut invocation = vec![args[0].clone()];
+        invocation.extend_from_slice(group);
+        invocations.push(invocation);
+    }
+    invocations
+}
+
+fn build_cli_app<'a, 'b>() -> App<'a, 'b> {
+    App::new(crate_name!())
+        .setting(AppSettings::VersionlessSubcommands)
+        .setting(AppSettings::AllowNegativeNumbers)
+        .setting(AppSettings::SubcommandRequired)
+        .setting(AppSettings::ColoredHelp)
+        .version(crate_version!())
+        .arg(custom_maskfile_path_arg())
+        .arg(introspect_arg())
+        .arg(prompt_arg())
+        .arg(yes_arg())
+        .arg(timeout_arg())
+        .arg(force_arg())
+        .arg(watch_arg())
 }
 
 fn find_maskfile() -> (Result<String, String>, String) {
@@ -79,6 +351,21 @@ fn find_maskfile() -> (Result<String, String>, String) {
     (maskfile, maskfile_path.to_str().unwrap().to_string())
 }
 
+fn is_starting_language_server() -> bool {
+    if env::args().nth(1).as_deref() != Some(lsp::NAME) {
+        return false;
+    }
+
+    // A command of the same name in the local maskfile takes precedence
+    match loader::read_maskfile(Path::new("./maskfile.md")) {
+        Ok(contents) => !mask_parser::parse(contents)
+            .commands
+            .iter()
+            .any(|c| c.has_name(lsp::NAME)),
+        Err(_) => true,
+    }
+}
+
 fn is_introspecting() -> bool {
     let args: Vec<String> = env::args().collect();
     for a in args {
@@ -109,26 +396,145 @@ fn introspect_arg<'a, 'b>() -> Arg<'a, 'b> {
         .multiple(false)
 }
 
+/// --prompt decides which args clap requires, so it's found before the app with the commands is
+/// built by parsing only the options before the command's name
+fn is_prompting(invocations: &[Vec<String>]) -> bool {
+    invocations.iter().any(|args| {
+        build_cli_app()
+            .setting(AppSettings::AllowExternalSubcommands)
+            .get_matches_from_safe(args)
+            .map(|matches| matches.is_present("prompt"))
+            .unwrap_or(false)
+    })
+}
+
+/// Prompt for missing required args and flags
+fn prompt_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("prompt")
+        .help("Prompt for missing required arguments and flags instead of failing")
+        .long("prompt")
+        .multiple(false)
+}
+
+/// Confirmations are skipped with --yes or by setting MASK_ASSUME_YES
+fn is_assuming_yes_by_env() -> bool {
+    env::var("MASK_ASSUME_YES")
+        .map(|val| !["", "0", "false"].contains(&val.as_str()))
+        .unwrap_or(false)
+}
+
+/// Skip the confirmation of commands with the confirm setting
+fn yes_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("yes")
+        .help("Run commands without asking for confirmation. Also set by MASK_ASSUME_YES.")
+        .long("yes")
+        .multiple(false)
+}
+
+/// Stop commands that run for longer than this
+fn timeout_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("timeout")
+        .help("Stop commands that run longer than this duration, like 10m or 1h 30m")
+        .long("timeout")
+        .takes_value(true)
+        .multiple(false)
+}
+
+/// Run commands even when their sources haven't changed
+fn force_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("force")
+        .help("Run commands even when they are up to date with their sources")
+        .long("force")
+        .multiple(false)
+}
+
+/// Run commands again whenever their files change
+fn watch_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("watch")
+        .help("Run commands again whenever the files they watch change")
+        .long("watch")
+        .multiple(false)
+}
+
+/// Finds the matches with one of mask's own options like --yes. They can be given before the
+/// command's name or after the name of the command or its parents, and the last one wins.
+/// Commands with a flag or arg of the same name get their own instead.
+fn find_option<'a, 'b>(
+    matches: &'b ArgMatches<'a>,
+    cmd: Option<&Command>,
+    subcommands: &[Command],
+    name: &str,
+) -> Option<&'b ArgMatches<'a>> {
+    let found = matches.subcommand_name().and_then(|sub_name| {
+        let c = subcommands.iter().find(|c| c.name == sub_name)?;
+        find_option(
+            matches.subcommand_matches(sub_name)?,
+            Some(c),
+            &c.subcommands,
+            name,
+        )
+    });
+    let is_own = cmd.is_none_or(|c| !has_arg_named(c, name));
+    found.or_else(|| (is_own && matches.is_present(name)).then_some(matches))
+}
+
+/// Whether the command has an arg or flag that would clash with one of mask's own options
+fn has_arg_named(cmd: &Command, name: &str) -> bool {
+    cmd.required_args.iter().any(|a| a.name == name)
+        || cmd.optional_args.iter().any(|a| a.name == name)
+        || cmd
+            .named_flags
+            .iter()
+            .any(|f| f.name == name || f.long == name)
+}
+
+/// Missing values can only be prompted for when opted into and running in a terminal
+fn prompts_for_missing_values(cmd: &Command, prompting: bool) -> bool {
+    (cmd.prompt || prompting) && prompt::is_interactive()
+}
+
 fn build_subcommands<'a, 'b>(
     mut cli_app: App<'a, 'b>,
-    subcommands: &'a Vec<Command>,
+    subcommands: &'a [Command],
+    examples: &'b [ExamplesHelp],
+    prompting: bool,
 ) -> App<'a, 'b> {
-    for c in subcommands {
+    for (c, examples) in subcommands.iter().zip(examples) {
         let mut subcmd = SubCommand::with_name(&c.name)
-            .about(c.description.as_ref())
+            .about(c.description.as_str())
             .setting(AppSettings::ColoredHelp)
             .setting(AppSettings::AllowNegativeNumbers);
+        // An alias that's taken by a sibling would run the wrong command, so it's left out
+        for alias in c
+            .aliases
+            .iter()
+            .filter(|a| !is_alias_taken(subcommands, c, a))
+        {
+            subcmd = subcmd.visible_alias(alias.as_str());
+        }
+        if c.hidden {
+            subcmd = subcmd.setting(AppSettings::Hidden);
+        }
         if !c.subcommands.is_empty() {
-            subcmd = build_subcommands(subcmd, &c.subcommands);
-            // If this parent command has no script, require a subcommand.
-            if c.script.is_none() {
+            subcmd = build_subcommands(subcmd, &c.subcommands, &examples.subcommands, prompting);
+            // If this parent command has no script to run or default subcommand, require a subcommand.
+            if (c.setup || c.scripts_for(env::consts::OS).is_empty())
+                && default_command(&c.subcommands).is_none()
+            {
                 subcmd = subcmd.setting(AppSettings::SubcommandRequired);
             }
         }
 
+        if let Some(help) = &examples.help {
+            subcmd = subcmd.after_help(help.as_str());
+        }
+
+        // Clap can't require the values that will be prompted for
+        let required = !prompts_for_missing_values(c, prompting);
+
         // Add all positional arguments
         for a in &c.required_args {
-            let arg = Arg::with_name(&a.name).required(true);
+            let arg = Arg::with_name(&a.name).required(required);
             subcmd = subcmd.arg(arg);
         }
 
@@ -146,16 +552,69 @@ fn build_subcommands<'a, 'b>(
                 .long(&f.long)
                 .takes_value(f.takes_value)
                 .multiple(f.multiple)
-                .required(f.required);
+                .required(f.required && required);
             subcmd = subcmd.arg(arg);
         }
+
+        // These can also be given after the command's name, unless it has its own of that name
+        if !has_arg_named(c, "yes") {
+            subcmd = subcmd.arg(yes_arg());
+        }
+        if !has_arg_named(c, "timeout") {
+            subcmd = subcmd.arg(timeout_arg());
+        }
         cli_app = cli_app.subcommand(subcmd);
     }
 
     cli_app
 }
 
-fn find_command<'a>(matches: &ArgMatches, subcommands: &Vec<Command>) -> Option<Command> {
+/// Whether the alias is the name of a sibling or an alias of one that comes before the command
+fn is_alias_taken(siblings: &[Command], cmd: &Command, alias: &str) -> bool {
+    siblings.iter().any(|s| s.name == alias)
+        || siblings
+            .iter()
+            .take_while(|s| s.span != cmd.span)
+            .any(|s| s.aliases.iter().any(|a| a == alias))
+}
+
+/// The examples section of a command's help output. clap only borrows the help text, so these
+/// are built up front in the same shape as the commands and kept for as long as the app lives.
+struct ExamplesHelp {
+    help: Option<String>,
+    subcommands: Vec<ExamplesHelp>,
+}
+
+fn examples_help(commands: &[Command]) -> Vec<ExamplesHelp> {
+    commands
+        .iter()
+        .map(|c| ExamplesHelp {
+            help: (!c.examples.is_empty()).then(|| format_examples(&c.examples)),
+            subcommands: examples_help(&c.subcommands),
+        })
+        .collect()
+}
+
+/// Formats a command's examples like the other sections of the help output
+fn format_examples(examples: &[Example]) -> String {
+    let examples: Vec<String> = examples
+        .iter()
+        .map(|e| {
+            e.source
+                .lines()
+                .map(|line| format!("    {}", line).trim_end().to_string())
+                .collect::<Vec<String>>()
+                .join("\n")
+        })
+        .collect();
+    format!("{}\n{}", "EXAMPLES:".yellow(), examples.join("\n\n"))
+}
+
+fn find_command(
+    matches: &ArgMatches,
+    subcommands: &[Command],
+    maskfile_path: &str,
+) -> Option<Command> {
     let mut command = None;
 
     // The child subcommand that was used
@@ -164,20 +623,100 @@ fn find_command<'a>(matches: &ArgMatches, subcommands: &Vec<Command>) -> Option<
             for c in subcommands {
                 if c.name == subcommand_name {
                     // Check if a subcommand was called, otherwise return this command
-                    command = find_command(matches, &c.subcommands)
-                        .or(Some(c.clone()).map(|c| get_command_options(c, &matches)));
+                    command = find_command(matches, &c.subcommands, maskfile_path).or_else(|| {
+                        if c.setup || c.scripts_for(env::consts::OS).is_empty() {
+                            find_default_command(matches, &c.subcommands, maskfile_path)
+                        } else {
+                            Some(get_command_options(c.clone(), matches, maskfile_path))
+                        }
+                    });
                 }
             }
         }
     }
 
-    return command;
+    command
+}
+
+/// The commands with scripts for the current OS, along with the parents of those
+fn commands_for_current_os(commands: &[Command]) -> Vec<Command> {
+    commands
+        .iter()
+        .filter_map(|c| {
+            let mut c = c.clone();
+            c.subcommands = commands_for_current_os(&c.subcommands);
+            let is_runnable = !c.scripts_for(env::consts::OS).is_empty();
+            (is_runnable || !c.subcommands.is_empty()).then_some(c)
+        })
+        .collect()
+}
+
+/// The command marked as the default. It's ignored if it can't be run without any args.
+fn default_command(subcommands: &[Command]) -> Option<&Command> {
+    subcommands.iter().find(|c| {
+        c.default && c.required_args.is_empty() && !c.named_flags.iter().any(|f| f.required)
+    })
+}
+
+/// Finds the command to run when no subcommand was given by following the default commands
+fn find_default_command(
+    matches: &ArgMatches,
+    subcommands: &[Command],
+    maskfile_path: &str,
+) -> Option<Command> {
+    let c = default_command(subcommands)?;
+    if c.setup || c.scripts_for(env::consts::OS).is_empty() {
+        find_default_command(matches, &c.subcommands, maskfile_path)
+    } else {
+        Some(get_command_options(c.clone(), matches, maskfile_path))
+    }
+}
+
+/// The parents of the command that are a setup for it, outermost first
+fn find_setup_commands(commands: &[Command], cmd: &Command) -> Vec<Command> {
+    let parent = match commands.iter().find(|c| contains_command(c, cmd)) {
+        Some(parent) if parent.span != cmd.span => parent,
+        _ => return vec![],
+    };
+
+    let mut setups = find_setup_commands(&parent.subcommands, cmd);
+    if parent.setup {
+        setups.insert(0, parent.clone());
+    }
+    setups
+}
+
+/// The names of the command and its parents, outermost first
+fn command_path(commands: &[Command], cmd: &Command) -> Vec<String> {
+    match commands.iter().find(|c| contains_command(c, cmd)) {
+        Some(c) if c.span == cmd.span => vec![c.name.clone()],
+        Some(c) => {
+            let mut path = command_path(&c.subcommands, cmd);
+            path.insert(0, c.name.clone());
+            path
+        }
+        None => vec![],
+    }
+}
+
+/// The command whose section the position is in, which is the last one that starts before it
+fn command_at(commands: &[Command], position: usize) -> Option<&Command> {
+    commands
+        .iter()
+        .flat_map(|c| std::iter::once(c).chain(command_at(&c.subcommands, position)))
+        .filter(|c| c.span.start <= position)
+        .max_by_key(|c| c.span.start)
 }
 
-fn get_command_options(mut cmd: Command, matches: &ArgMatches) -> Command {
+/// Whether the command is c or one of its subcommands
+fn contains_command(c: &Command, cmd: &Command) -> bool {
+    c.span == cmd.span || c.subcommands.iter().any(|sub| contains_command(sub, cmd))
+}
+
+fn get_command_options(mut cmd: Command, matches: &ArgMatches, maskfile_path: &str) -> Command {
     // Check all required args
     for arg in &mut cmd.required_args {
-        arg.val = matches.value_of(arg.name.clone()).unwrap().to_string();
+        arg.val = matches.value_of(arg.name.clone()).unwrap_or("").to_string();
     }
 
     // Check optional args
@@ -194,28 +733,30 @@ fn get_command_options(mut cmd: Command, matches: &ArgMatches) -> Command {
             // Extract the value
             let raw_value = matches
                 .value_of(flag.name.clone())
-                .or(Some(""))
-                .unwrap()
+                .unwrap_or("")
                 .to_string();
 
-            if !flag.choices.is_empty() && raw_value != "" {
-                if !flag.choices.iter().any(|choice| choice == &raw_value) {
-                    eprintln!(
-                        "{} flag `{}` expects one of {:?}",
-                        "ERROR:".red(),
-                        flag.name,
-                        flag.choices,
-                    );
-                    std::process::exit(1);
-                }
+            if !flag.choices.is_empty()
+                && !raw_value.is_empty()
+                && !flag.choices.iter().any(|choice| choice == &raw_value)
+            {
+                eprintln!(
+                    "{} {}: flag `{}` expects one of {:?}",
+                    "ERROR:".red(),
+                    locate::location(maskfile_path, &flag.span),
+                    flag.name,
+                    flag.choices,
+                );
+                std::process::exit(1);
             }
 
-            if flag.validate_as_number && raw_value != "" {
+            if flag.validate_as_number && !raw_value.is_empty() {
                 // Try converting to an integer or float to validate it
                 if raw_value.parse::<isize>().is_err() && raw_value.parse::<f32>().is_err() {
                     eprintln!(
-                        "{} flag `{}` expects a numerical value",
+                        "{} {}: flag `{}` expects a numerical value",
                         "ERROR:".red(),
+                        locate::location(maskfile_path, &flag.span),
                         flag.name
                     );
                     std::process::exit(1);
@@ -226,12 +767,11 @@ fn get_command_options(mut cmd: Command, matches: &ArgMatches) -> Command {
         } else {
             // Check if the boolean flag is present and set to "true".
             // It's a string since it's set as an environment variable.
-            let val = if matches.is_present(flag.name.clone()) {
+            if matches.is_present(flag.name.clone()) {
                 "true".to_string()
             } else {
                 "".to_string()
-            };
-            val
+            }
         };
     }
 
@@ -0,0 +1,136 @@
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, FuzzySelect, Input, Password, Select};
+use mask_parser::maskfile::{Command, NamedFlag};
+use std::env;
+use std::io::{self, IsTerminal};
+
+/// Prompts are only shown when a person is there to answer them
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// Asks the user to confirm running the command. Always refuses when there's no terminal to ask in.
+pub fn confirm(cmd: &Command, message: &str) -> dialoguer::Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+
+    let message = if message.is_empty() {
+        format!("Are you sure you want to run `{}`?", cmd.name)
+    } else {
+        message.to_string()
+    };
+    Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(message)
+        .default(false)
+        .interact()
+}
+
+/// Lets the user fuzzy search the runnable commands and then asks for their required values.
+/// Returns None when there's nothing to run or the user backs out with escape.
+pub fn pick_command(commands: &[Command]) -> dialoguer::Result<Option<Command>> {
+    let mut choices = vec![];
+    collect_runnable_commands(commands, "", &mut choices);
+    if choices.is_empty() {
+        return Ok(None);
+    }
+
+    let width = choices
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    let items: Vec<String> = choices
+        .iter()
+        .map(|(name, c)| format!("{:width$}  {}", name, c.description, width = width))
+        .map(|item| item.trim_end().to_string())
+        .collect();
+
+    let picked = FuzzySelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Which command do you want to run?")
+        .items(&items)
+        .default(0)
+        .interact_opt()?;
+
+    match picked {
+        Some(index) => {
+            let mut cmd = choices[index].1.clone();
+            prompt_missing_values(&mut cmd)?;
+            Ok(Some(cmd))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Collects the commands with a script to run on this OS along with their full names
+fn collect_runnable_commands<'a>(
+    commands: &'a [Command],
+    parent_name: &str,
+    choices: &mut Vec<(String, &'a Command)>,
+) {
+    for c in commands.iter().filter(|c| !c.hidden) {
+        let name = format!("{} {}", parent_name, c.name).trim().to_string();
+        if !c.setup && !c.scripts_for(env::consts::OS).is_empty() {
+            choices.push((name.clone(), c));
+        }
+        collect_runnable_commands(&c.subcommands, &name, choices);
+    }
+}
+
+/// Asks for every required arg and flag of the command that wasn't given a value
+pub fn prompt_missing_values(cmd: &mut Command) -> dialoguer::Result<()> {
+    for arg in cmd.required_args.iter_mut().filter(|a| a.val.is_empty()) {
+        arg.val = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(&arg.name)
+            .interact_text()?;
+    }
+
+    for flag in cmd
+        .named_flags
+        .iter_mut()
+        .filter(|f| f.required && f.val.is_empty())
+    {
+        flag.val = prompt_flag_value(flag)?;
+    }
+
+    Ok(())
+}
+
+fn prompt_flag_value(flag: &NamedFlag) -> dialoguer::Result<String> {
+    let theme = ColorfulTheme::default();
+
+    if !flag.choices.is_empty() {
+        let index = Select::with_theme(&theme)
+            .with_prompt(&flag.name)
+            .items(&flag.choices)
+            .default(0)
+            .interact()?;
+        return Ok(flag.choices[index].clone());
+    }
+
+    if !flag.takes_value {
+        // Boolean flags are set to "true" when present
+        let present = Confirm::with_theme(&theme)
+            .with_prompt(&flag.name)
+            .interact()?;
+        return Ok(if present { "true" } else { "" }.to_string());
+    }
+
+    if flag.secret {
+        return Password::with_theme(&theme)
+            .with_prompt(&flag.name)
+            .interact();
+    }
+
+    let validate_as_number = flag.validate_as_number;
+    Input::with_theme(&theme)
+        .with_prompt(&flag.name)
+        .validate_with(move |val: &String| {
+            if validate_as_number && val.parse::<isize>().is_err() && val.parse::<f32>().is_err() {
+                Err("expects a numerical value")
+            } else {
+                Ok(())
+            }
+        })
+        .interact_text()
+}
@@ -0,0 +1,153 @@
+use mask_parser::maskfile::{Command, Requirement};
+use std::env;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Checks that every program the command requires is in PATH with a matching version
+pub fn check(cmd: &Command) -> Result<()> {
+    for entry in &cmd.requires {
+        let requirement = match Requirement::parse(entry) {
+            Some(requirement) => requirement,
+            None => continue,
+        };
+
+        let path = find_program(requirement.program).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "`{}` needs `{}` (not found in PATH)",
+                    cmd.name, requirement.program
+                ),
+            )
+        })?;
+
+        if let Some((comparison, version)) = requirement.version {
+            let found = program_version(&path);
+            let reason = match found {
+                Some(found) if matches_version(&found, comparison, version) => continue,
+                Some(found) => format!("found {}", format_version(&found)),
+                None => "couldn't find its version".to_string(),
+            };
+            return Err(Error::other(format!(
+                "`{}` needs `{}` ({})",
+                cmd.name, entry, reason
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn find_program(program: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .flat_map(|dir| program_names(program).map(move |name| dir.join(name)))
+        .find(|path| is_executable(path))
+}
+
+#[cfg(not(windows))]
+fn program_names(program: &str) -> impl Iterator<Item = String> {
+    std::iter::once(program.to_string())
+}
+
+/// Programs can be named without an extension on windows, like `node` for `node.exe`
+#[cfg(windows)]
+fn program_names(program: &str) -> impl Iterator<Item = String> {
+    let extensions = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    let names: Vec<String> = std::iter::once(program.to_string())
+        .chain(
+            extensions
+                .split(';')
+                .map(|ext| format!("{}{}", program, ext)),
+        )
+        .collect();
+    names.into_iter()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The first version in the output of `program --version`. Some programs print it to stderr.
+fn program_version(path: &Path) -> Option<Vec<u64>> {
+    let output = process::Command::new(path).arg("--version").output().ok()?;
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    parse_version(&text)
+}
+
+/// Finds the first run of numbers separated by dots, like `18.17.0` in `v18.17.0`
+fn parse_version(text: &str) -> Option<Vec<u64>> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let version = text[start..]
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .next()?;
+    Some(
+        version
+            .split('.')
+            .map_while(|part| part.parse().ok())
+            .collect(),
+    )
+}
+
+fn format_version(version: &[u64]) -> String {
+    let parts: Vec<String> = version.iter().map(|part| part.to_string()).collect();
+    parts.join(".")
+}
+
+/// Only as many parts as the wanted version has are compared, so 18.17.0 is `=18` and not `>18`
+fn matches_version(found: &[u64], comparison: &str, wanted: &str) -> bool {
+    let wanted: Vec<u64> = wanted.split('.').map(|p| p.parse().unwrap_or(0)).collect();
+    let found: Vec<u64> = (0..wanted.len())
+        .map(|i| found.get(i).copied().unwrap_or(0))
+        .collect();
+
+    let ordering = found.cmp(&wanted);
+    match comparison {
+        ">=" => ordering.is_ge(),
+        ">" => ordering.is_gt(),
+        "<=" => ordering.is_le(),
+        "<" => ordering.is_lt(),
+        _ => ordering.is_eq(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions_from_the_version_output() {
+        assert_eq!(Some(vec![18, 17, 0]), parse_version("v18.17.0\n"));
+        assert_eq!(Some(vec![1, 6]), parse_version("jq-1.6"));
+        assert_eq!(
+            Some(vec![24, 0, 7]),
+            parse_version("Docker version 24.0.7, build afdd53b")
+        );
+        assert_eq!(None, parse_version("no version here"));
+    }
+
+    #[test]
+    fn compares_as_many_parts_as_wanted() {
+        assert!(matches_version(&[18, 17, 0], ">=", "18"));
+        assert!(matches_version(&[18, 17, 0], "=", "18"));
+        assert!(!matches_version(&[18, 17, 0], ">", "18"));
+        assert!(matches_version(&[18, 17, 0], ">", "18.2"));
+        assert!(!matches_version(&[16, 20], ">=", "18"));
+        assert!(matches_version(&[16, 20], "<", "18"));
+        assert!(matches_version(&[2], "<=", "2.0"));
+    }
+}
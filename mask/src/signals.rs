@@ -0,0 +1,113 @@
+use std::process::Child;
+#[cfg(unix)]
+use std::sync::Mutex;
+
+/// Who the signals mask receives are forwarded to
+#[cfg(unix)]
+static STATE: Mutex<State> = Mutex::new(State {
+    target: 0,
+    starting: false,
+    pending: None,
+});
+
+#[cfg(unix)]
+struct State {
+    /// The pid of the running script, or minus its process group id when it runs in its own
+    /// group. 0 means no script is running.
+    target: i32,
+    /// A script is being started, so signals wait for it instead of stopping mask
+    starting: bool,
+    /// A signal that arrived while the script was being started, and whether another process
+    /// sent it
+    pending: Option<(i32, bool)>,
+}
+
+/// Forwards SIGINT, SIGTERM and SIGHUP to the running script for as long as it's kept alive, so
+/// mask doesn't exit before the script had a chance to stop. It must be prepared before the
+/// script is started, so the signals that arrive in the meantime aren't lost.
+pub struct Forwarding;
+
+impl Forwarding {
+    pub fn prepare() -> Self {
+        #[cfg(unix)]
+        {
+            static LISTEN: std::sync::Once = std::sync::Once::new();
+            LISTEN.call_once(listen_on_unix);
+            STATE.lock().unwrap().starting = true;
+        }
+
+        Forwarding
+    }
+
+    pub fn start(&self, child: &Child, own_group: bool) {
+        #[cfg(unix)]
+        {
+            let pid = child.id() as i32;
+            let mut state = STATE.lock().unwrap();
+            state.target = if own_group { -pid } else { pid };
+            state.starting = false;
+            if let Some((signal, sent)) = state.pending.take() {
+                forward(state.target, signal, sent);
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = (child, own_group);
+    }
+}
+
+impl Drop for Forwarding {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            let mut state = STATE.lock().unwrap();
+            state.target = 0;
+            state.starting = false;
+            // The script never started, so mask stops like it would without forwarding
+            if let Some((signal, _)) = state.pending.take() {
+                drop(state);
+                let _ = signal_hook::low_level::emulate_default_handler(signal);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn listen_on_unix() {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    use signal_hook::iterator::exfiltrator::WithOrigin;
+    use signal_hook::iterator::SignalsInfo;
+    use signal_hook::low_level::siginfo::Cause;
+
+    let mut signals = match SignalsInfo::<WithOrigin>::new([SIGINT, SIGTERM, SIGHUP]) {
+        Ok(signals) => signals,
+        Err(_) => return,
+    };
+
+    std::thread::spawn(move || {
+        for origin in signals.forever() {
+            let sent = matches!(origin.cause, Cause::Sent(_));
+            let mut state = STATE.lock().unwrap();
+            match state.target {
+                0 if state.starting => state.pending = Some((origin.signal, sent)),
+                // Without a script to stop, mask stops like it would without forwarding
+                0 => {
+                    drop(state);
+                    let _ = signal_hook::low_level::emulate_default_handler(origin.signal);
+                }
+                target => forward(target, origin.signal, sent),
+            }
+        }
+    });
+}
+
+#[cfg(unix)]
+fn forward(target: i32, signal: i32, sent: bool) {
+    // Signals from the terminal like Ctrl-C already reach every process in the foreground
+    // group, so only the ones sent to mask by another process are forwarded
+    if target > 0 && !sent {
+        return;
+    }
+    unsafe {
+        libc::kill(target, signal);
+    }
+}
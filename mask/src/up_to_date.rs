@@ -0,0 +1,185 @@
+use colored::*;
+use glob::{MatchOptions, Pattern};
+use mask_parser::maskfile::Command;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Where mask keeps its state, next to the maskfile
+const STATE_DIR: &str = ".mask";
+
+/// Checks if the command's sources haven't changed since its outputs were made or, when it has
+/// no outputs, since it last ran successfully. It also has to have last run with the same args
+/// and flags. Commands without sources are never up to date.
+///
+/// The path is the names of the command and its parents, which its fingerprint is kept under.
+pub fn is_up_to_date(cmd: &Command, path: &[String], maskfile_path: &str) -> Result<bool> {
+    let dir = maskfile_dir(maskfile_path);
+    let sources = find_files(&dir, &cmd.sources)?;
+    if sources.is_empty() {
+        if !cmd.sources.is_empty() {
+            eprintln!(
+                "{} `{}` has no files matching its `sources`, so it always runs",
+                "WARNING:".yellow(),
+                cmd.name
+            );
+        }
+        return Ok(false);
+    }
+
+    let saved = fs::read_to_string(fingerprint_path(&dir, path)).ok();
+    if saved != Some(fingerprint(cmd, &dir, &sources)?) {
+        return Ok(false);
+    }
+    if cmd.outputs.is_empty() {
+        return Ok(true);
+    }
+
+    // Every output has to exist and be newer than all of the sources
+    let mut oldest_output = None;
+    for pattern in &cmd.outputs {
+        let outputs = find_files(&dir, std::slice::from_ref(pattern))?;
+        if outputs.is_empty() {
+            return Ok(false);
+        }
+        for output in outputs {
+            let modified = modified(&output)?;
+            oldest_output = Some(oldest_output.map_or(modified, |t: SystemTime| t.min(modified)));
+        }
+    }
+    let mut newest_source = SystemTime::UNIX_EPOCH;
+    for source in &sources {
+        newest_source = newest_source.max(modified(source)?);
+    }
+
+    Ok(oldest_output.is_some_and(|oldest| oldest >= newest_source))
+}
+
+/// Saves the fingerprint of a command with sources once it ran successfully
+pub fn save_fingerprint(cmd: &Command, path: &[String], maskfile_path: &str) -> Result<()> {
+    if cmd.sources.is_empty() {
+        return Ok(());
+    }
+
+    let dir = maskfile_dir(maskfile_path);
+    let sources = find_files(&dir, &cmd.sources)?;
+    let fingerprint_path = fingerprint_path(&dir, path);
+    if let Some(parent) = fingerprint_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(fingerprint_path, fingerprint(cmd, &dir, &sources)?)
+}
+
+/// The dir is `.` rather than empty for a maskfile in the current dir, so it can be walked
+fn maskfile_dir(maskfile_path: &str) -> PathBuf {
+    match Path::new(maskfile_path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// The files matching any of the globs relative to the dir, sorted so fingerprints are stable.
+/// Files are matched by walking the dir like `watch` does, since `glob::glob` only returns
+/// directories for a trailing `**` like `src/**`.
+fn find_files(dir: &Path, patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    let mut files = vec![];
+    for pattern in patterns {
+        let glob = Pattern::new(pattern).map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("`{}` is not a valid glob: {}", pattern, err.msg),
+            )
+        })?;
+        // Only the part of the glob before any wildcards has to be walked
+        let literal: PathBuf = Path::new(pattern)
+            .components()
+            .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+            .collect();
+        let mut found = vec![];
+        add_files(&dir.join(literal), &mut found)?;
+        files.extend(found.into_iter().filter(|path| {
+            path.strip_prefix(dir)
+                .is_ok_and(|path| glob.matches_path_with(path, options))
+        }));
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Adds the file, or every file within the directory
+fn add_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    // Symlinked directories aren't followed so a link to a parent can't loop forever
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            add_files(&entry?.path(), files)?;
+        }
+    } else if path.is_file() {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+fn modified(path: &Path) -> Result<SystemTime> {
+    fs::metadata(path)?.modified()
+}
+
+/// Fingerprints are kept per command, with the names of its parents so subcommands of the same
+/// name don't overwrite each other, like `docker.build` and `web.build`
+fn fingerprint_path(dir: &Path, path: &[String]) -> PathBuf {
+    let names: Vec<String> = path
+        .iter()
+        .map(|name| {
+            name.chars()
+                .map(|c| if c.is_alphanumeric() { c } else { '-' })
+                .collect()
+        })
+        .collect();
+    dir.join(STATE_DIR)
+        .join("fingerprints")
+        .join(names.join("."))
+}
+
+/// A hash of the command's scripts, the values of its args and flags, and the contents of its
+/// sources when it has no outputs. Commands with outputs are compared with those instead.
+fn fingerprint(cmd: &Command, dir: &Path, sources: &[PathBuf]) -> Result<String> {
+    let mut hasher = Sha256::new();
+    for script in &cmd.scripts {
+        hasher.update(script.executor.as_bytes());
+        hasher.update([0]);
+        hasher.update(script.source.as_bytes());
+        hasher.update([0]);
+    }
+
+    let args = cmd.required_args.iter().map(|a| (&a.name, &a.val));
+    let optional_args = cmd.optional_args.iter().map(|a| (&a.name, &a.val));
+    let flags = cmd.named_flags.iter().map(|f| (&f.name, &f.val));
+    for (name, val) in args.chain(optional_args).chain(flags) {
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+        hasher.update(val.as_bytes());
+        hasher.update([0]);
+    }
+
+    if cmd.outputs.is_empty() {
+        for source in sources {
+            let relative = source.strip_prefix(dir).unwrap_or(source);
+            hasher.update(relative.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            hasher.update(fs::read(source)?);
+            hasher.update([0]);
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,217 @@
+use colored::*;
+use glob::Pattern;
+use mask_parser::maskfile::Command;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::env;
+use std::fs::canonicalize;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+use std::process::{self, Child};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+#[cfg(unix)]
+use wait_timeout::ChildExt;
+
+/// How long the files have to stay unchanged before the commands run again
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// How long a run that's still going has to exit after SIGTERM before it's killed
+#[cfg(unix)]
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Runs mask again with the same args but --watch whenever a file the commands watch changes,
+/// stopping the previous run if it's still going. It only returns when watching fails.
+pub fn watch(cmds: &[Command], maskfile_path: &str) -> Result<()> {
+    let dir = canonicalize(Path::new(maskfile_path))?
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let filter = Filter::new(cmds, &dir)?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(watch_error)?;
+    for root in filter.roots() {
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(watch_error)?;
+    }
+
+    let args = args_without_watch();
+    loop {
+        let mut child = process::Command::new(env::current_exe()?)
+            .args(&args)
+            .spawn()?;
+        let changed = wait_for_change(&rx, &filter, &mut child)?;
+        stop(&mut child)?;
+        let changed = changed.strip_prefix(&dir).unwrap_or(&changed);
+        eprintln!(
+            "{} `{}` changed, running again",
+            "WATCHING:".cyan(),
+            changed.display()
+        );
+    }
+}
+
+/// The args mask was called with, without the first --watch since it's one of mask's own
+fn args_without_watch() -> Vec<String> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if let Some(idx) = args.iter().position(|arg| arg == "--watch") {
+        args.remove(idx);
+    }
+    args
+}
+
+/// Waits for a watched file to change and for the changes to settle down
+fn wait_for_change(
+    rx: &Receiver<notify::Result<Event>>,
+    filter: &Filter,
+    child: &mut Child,
+) -> Result<PathBuf> {
+    let mut exited = false;
+    let changed = loop {
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(Ok(event)) => {
+                let is_change = matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                );
+                if let Some(path) = event.paths.iter().find(|p| is_change && filter.matches(p)) {
+                    break path.clone();
+                }
+            }
+            Ok(Err(_)) => (),
+            Err(RecvTimeoutError::Timeout) => {
+                if !exited && child.try_wait()?.is_some() {
+                    exited = true;
+                    eprintln!("{} waiting for changes", "WATCHING:".cyan());
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(Error::other("stopped watching for changes"));
+            }
+        }
+    };
+
+    while rx.recv_timeout(DEBOUNCE).is_ok() {}
+    Ok(changed)
+}
+
+/// Asks the run to stop, which mask passes on to its script, and kills it after a grace period
+fn stop(child: &mut Child) -> Result<()> {
+    if child.try_wait()?.is_some() {
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    {
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) };
+        if child.wait_timeout(STOP_GRACE_PERIOD)?.is_some() {
+            return Ok(());
+        }
+    }
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+fn watch_error(err: notify::Error) -> Error {
+    Error::other(format!("couldn't watch for changes: {}", err))
+}
+
+/// Decides which changed files make the commands run again
+struct Filter {
+    dir: PathBuf,
+    /// The `watch` globs of the commands, or their `sources` when they don't have any
+    patterns: Vec<Pattern>,
+    /// Changes to the `outputs` of the commands are ignored so they don't run again and again
+    outputs: Vec<Pattern>,
+}
+
+impl Filter {
+    fn new(cmds: &[Command], dir: &Path) -> Result<Self> {
+        let mut patterns = vec![];
+        for c in cmds {
+            let globs = if c.watch.is_empty() {
+                &c.sources
+            } else {
+                &c.watch
+            };
+            // Watching everything would include whatever the command writes, like build output,
+            // and run it again and again
+            if globs.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "`{}` has no files to watch. Add the `watch` or `sources` setting.",
+                        c.name
+                    ),
+                ));
+            }
+            for pattern in globs {
+                patterns.push(parse_pattern(pattern)?);
+            }
+        }
+        let outputs = cmds
+            .iter()
+            .flat_map(|c| &c.outputs)
+            .map(|pattern| parse_pattern(pattern))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            patterns,
+            outputs,
+        })
+    }
+
+    /// The directories to watch, which are the parts of the globs before any wildcards
+    fn roots(&self) -> Vec<PathBuf> {
+        let mut roots: Vec<PathBuf> = self
+            .patterns
+            .iter()
+            .map(|pattern| {
+                let literal: PathBuf = Path::new(pattern.as_str())
+                    .components()
+                    .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+                    .collect();
+                let mut root = self.dir.join(literal);
+                // Files are often replaced when saved, so their directory is watched instead
+                while !root.is_dir() && root != self.dir {
+                    root.pop();
+                }
+                root
+            })
+            .collect();
+        roots.sort();
+        roots.dedup();
+        roots
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let path = match path.strip_prefix(&self.dir) {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+
+        // Like .git, .mask and the swap files of editors
+        let is_hidden = path.components().any(|c| match c {
+            Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+            _ => false,
+        });
+        if is_hidden || self.outputs.iter().any(|p| p.matches_path(path)) {
+            return false;
+        }
+
+        self.patterns.iter().any(|p| p.matches_path(path))
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Result<Pattern> {
+    Pattern::new(pattern).map_err(|err| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("`{}` is not a valid glob: {}", pattern, err.msg),
+        )
+    })
+}
@@ -401,8 +401,9 @@ Write-Output "This shouldn't render"
             .cli("notanumber --val a234")
             .assert()
             .stderr(contains(format!(
-                "{} flag `val` expects a numerical value",
-                "ERROR:".red()
+                "{} {}:5: flag `val` expects a numerical value",
+                "ERROR:".red(),
+                maskfile_path.display()
             )))
             .failure();
     }
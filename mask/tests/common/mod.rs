@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use assert_cmd::{crate_name, prelude::*};
 use assert_fs::prelude::*;
 use std::path::PathBuf;
@@ -0,0 +1,108 @@
+mod common;
+use assert_cmd::prelude::*;
+use colored::*;
+use common::MaskCommandExt;
+use predicates::str::contains;
+use std::fs;
+
+const UNFORMATTED: &str = r#"# Tasks
+## serve   (port)
+> Serve the app
+
+**OPTIONS**
+* env
+    * desc: Which environment to use
+    * flags: --env -e
+    * type: string
+~~~bash
+echo "Serving on port $port"
+~~~
+"#;
+
+const FORMATTED: &str = r#"# Tasks
+
+## serve (port)
+
+> Serve the app
+
+**OPTIONS**
+* env
+    * flags: -e --env
+    * type: string
+    * desc: Which environment to use
+
+```bash
+echo "Serving on port $port"
+```
+"#;
+
+#[test]
+fn formats_the_maskfile_in_place() {
+    let (_temp, maskfile_path) = common::maskfile(UNFORMATTED);
+
+    common::run_mask(&maskfile_path)
+        .command("fmt")
+        .assert()
+        .code(0)
+        .success();
+
+    assert_eq!(FORMATTED, fs::read_to_string(&maskfile_path).unwrap());
+}
+
+mod check {
+    use super::*;
+
+    #[test]
+    fn exits_with_error_and_prints_a_diff_when_not_formatted() {
+        let (_temp, maskfile_path) = common::maskfile(UNFORMATTED);
+
+        common::run_mask(&maskfile_path)
+            .cli("fmt --check")
+            .assert()
+            .code(1)
+            .stdout(contains("-## serve   (port)".red().to_string()))
+            .stdout(contains("+## serve (port)".green().to_string()))
+            .stderr(contains(format!(
+                "{} maskfile is not formatted",
+                "ERROR:".red()
+            )))
+            .failure();
+
+        // The maskfile must be left untouched
+        assert_eq!(UNFORMATTED, fs::read_to_string(&maskfile_path).unwrap());
+    }
+
+    #[test]
+    fn succeeds_when_already_formatted() {
+        let (_temp, maskfile_path) = common::maskfile(FORMATTED);
+
+        common::run_mask(&maskfile_path)
+            .cli("fmt --check")
+            .assert()
+            .code(0)
+            .success();
+    }
+}
+
+#[test]
+fn maskfile_commands_take_precedence_over_fmt() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## fmt
+
+~~~bash
+echo "custom fmt"
+~~~
+
+~~~powershell
+Write-Output "custom fmt"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .command("fmt")
+        .assert()
+        .stdout(contains("custom fmt"))
+        .success();
+}
@@ -186,3 +186,373 @@ Exit 2
             .failure();
     }
 }
+
+#[cfg(not(windows))]
+mod runs_multiple_code_blocks_in_sequence {
+    use super::*;
+
+    #[test]
+    fn runs_every_code_block_in_order() {
+        let (_temp, maskfile_path) = common::maskfile(
+            r#"
+## build (name)
+
+~~~sh
+echo "Setting up $name"
+~~~
+
+~~~js
+console.log(`Building ${process.env.name}`);
+~~~
+"#,
+        );
+
+        common::run_mask(&maskfile_path)
+            .cli("build app")
+            .assert()
+            .stdout("Setting up app\nBuilding app\n")
+            .success();
+    }
+
+    #[test]
+    fn stops_at_the_first_code_block_that_fails() {
+        let (_temp, maskfile_path) = common::maskfile(
+            r#"
+## build
+
+~~~sh
+echo "Setting up"
+exit 3
+~~~
+
+~~~sh
+echo "Building"
+~~~
+"#,
+        );
+
+        common::run_mask(&maskfile_path)
+            .command("build")
+            .assert()
+            .stdout("Setting up\n")
+            .code(3)
+            .failure();
+    }
+}
+
+#[cfg(not(windows))]
+mod runs_multiple_commands_separated_by_a_comma {
+    use super::*;
+
+    const MASKFILE: &str = r#"
+## build
+
+**OPTIONS**
+* release
+    * flags: --release
+
+~~~sh
+echo "Building release=$release"
+~~~
+
+## test (suite)
+
+~~~sh
+echo "Testing $suite"
+~~~
+
+## fail
+
+~~~sh
+exit 4
+~~~
+"#;
+
+    #[test]
+    fn runs_each_command_with_its_own_args() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .cli("build --release , test unit")
+            .assert()
+            .stdout("Building release=true\nTesting unit\n")
+            .success();
+    }
+
+    #[test]
+    fn stops_at_the_first_command_that_fails() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .cli("build , fail , test unit")
+            .assert()
+            .stdout("Building release=\n")
+            .code(4)
+            .failure();
+    }
+
+    #[test]
+    fn runs_nothing_when_a_command_is_not_found() {
+        let (_temp, maskfile_path) = common::maskfile(
+            r#"
+## build
+
+~~~sh
+echo "Building"
+~~~
+"#,
+        );
+
+        common::run_mask(&maskfile_path)
+            .cli("build , tset")
+            .assert()
+            .stdout("")
+            .stderr(contains("Found argument 'tset' which wasn't expected"))
+            .failure();
+    }
+}
+
+#[cfg(not(windows))]
+mod retries {
+    use super::*;
+
+    const MASKFILE: &str = r#"
+## flaky
+
+**SETTINGS**
+* retries: 2
+* retry_delay: 10ms
+* retry_backoff
+
+~~~sh
+count=$(cat "$MASKFILE_DIR/count" 2>/dev/null || echo 0)
+count=$((count + 1))
+echo "$count" > "$MASKFILE_DIR/count"
+echo "Attempt $count"
+[ "$count" -ge 3 ] || exit $((count + 10))
+~~~
+
+## flakier
+
+**SETTINGS**
+* retries: 1
+
+~~~sh
+count=$(cat "$MASKFILE_DIR/count" 2>/dev/null || echo 0)
+count=$((count + 1))
+echo "$count" > "$MASKFILE_DIR/count"
+echo "Attempt $count"
+[ "$count" -ge 3 ] || exit $((count + 10))
+~~~
+"#;
+
+    #[test]
+    fn retries_until_the_command_succeeds() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .command("flaky")
+            .assert()
+            .stdout("Attempt 1\nAttempt 2\nAttempt 3\n")
+            .stderr(contains(
+                "`flaky` failed with exit status: 11, retrying in 10ms (attempt 2 of 3)",
+            ))
+            // The delay doubles after every attempt
+            .stderr(contains(
+                "`flaky` failed with exit status: 12, retrying in 20ms (attempt 3 of 3)",
+            ))
+            .success();
+    }
+
+    #[test]
+    fn exits_with_the_code_of_the_last_attempt() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .command("flakier")
+            .assert()
+            .stdout("Attempt 1\nAttempt 2\n")
+            .code(12)
+            .failure();
+    }
+}
+
+#[cfg(not(windows))]
+mod finally_blocks {
+    use super::*;
+
+    const MASKFILE: &str = r#"
+## deploy (code)
+
+~~~sh
+echo "Deploying"
+exit $code
+~~~
+
+~~~sh finally
+echo "Cleaning up after $MASK_EXIT_CODE"
+~~~
+
+## broken_cleanup
+
+~~~sh
+echo "Deploying"
+~~~
+
+~~~sh finally
+exit 7
+~~~
+"#;
+
+    #[test]
+    fn runs_after_the_command_succeeds() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .cli("deploy 0")
+            .assert()
+            .stdout("Deploying\nCleaning up after 0\n")
+            .success();
+    }
+
+    #[test]
+    fn runs_after_the_command_fails_and_keeps_its_exit_code() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .cli("deploy 3")
+            .assert()
+            .stdout("Deploying\nCleaning up after 3\n")
+            .code(3)
+            .failure();
+    }
+
+    #[test]
+    fn fails_the_command_when_the_cleanup_fails() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .command("broken_cleanup")
+            .assert()
+            .stdout("Deploying\n")
+            .code(7)
+            .failure();
+    }
+}
+
+#[cfg(not(windows))]
+mod mask_env {
+    use super::*;
+
+    const MASKFILE: &str = r#"
+## release
+
+~~~sh
+echo "VERSION=1.2.3" >> "$MASK_ENV"
+~~~
+
+~~~sh
+echo "Tagging $VERSION"
+~~~
+
+## version
+
+~~~sh
+echo "VERSION=2.0.0" >> "$MASK_ENV"
+echo "not a variable" >> "$MASK_ENV"
+~~~
+
+## publish
+
+~~~sh
+echo "Publishing ${VERSION:-nothing}"
+~~~
+"#;
+
+    #[test]
+    fn passes_variables_on_to_the_next_code_block() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .command("release")
+            .assert()
+            .stdout("Tagging 1.2.3\n")
+            .success();
+    }
+
+    #[test]
+    fn passes_variables_on_to_the_next_command() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .cli("version , publish")
+            .assert()
+            .stdout("Publishing 2.0.0\n")
+            .success();
+    }
+
+    #[test]
+    fn does_not_pass_variables_on_to_other_runs() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .command("version")
+            .assert()
+            .success();
+
+        common::run_mask(&maskfile_path)
+            .command("publish")
+            .assert()
+            .stdout("Publishing nothing\n")
+            .success();
+    }
+}
+
+mod examples {
+    use super::*;
+
+    const MASKFILE: &str = r#"
+## serve (port)
+
+```json
+{ "port": 8080 }
+```
+
+```bash norun
+mask serve 8080
+```
+
+```bash
+echo "Serving on port $port"
+```
+
+```powershell
+Write-Output "Serving on port $env:port"
+```
+"#;
+
+    #[test]
+    fn shows_examples_in_the_help_output() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .cli("serve --help")
+            .assert()
+            .stdout(contains(format!(
+                "{}\n    {{ \"port\": 8080 }}\n\n    mask serve 8080\n",
+                "EXAMPLES:".yellow()
+            )))
+            .success();
+    }
+
+    #[test]
+    fn does_not_run_examples() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .cli("serve 8080")
+            .assert()
+            .stdout(contains("Serving on port 8080"))
+            .success();
+    }
+}
@@ -26,8 +26,19 @@ echo something
         "multiple": false,
         "takes_value": false,
         "required": false,
+        "secret": false,
         "validate_as_number": false,
         "choices": [],
+        "span": { "start": 0, "end": 0, "line": 0 },
+    });
+
+    let script = json!({
+        "executor": "bash",
+        "finally": false,
+        "source": "echo something\n",
+        "span": { "start": 61, "end": 87, "line": 7 },
+        "os": null,
+        "attributes": {},
     });
 
     let expected_json = json!({
@@ -35,13 +46,27 @@ echo something
         "description": "",
         "commands": [
             {
+                "span": { "start": 19, "end": 34, "line": 4 },
                 "level": 2,
                 "name": "somecommand",
                 "description": "The command description",
-                "script": {
-                    "executor": "bash",
-                    "source": "echo something\n",
-                },
+                "aliases": [],
+                "hidden": false,
+                "default": false,
+                "prompt": false,
+                "confirm": null,
+                "timeout": null,
+                "retries": 0,
+                "retry_delay": null,
+                "retry_backoff": false,
+                "setup": false,
+                "requires": [],
+                "preconditions": [],
+                "sources": [],
+                "outputs": [],
+                "watch": [],
+                "scripts": [script],
+                "examples": [],
                 "subcommands": [],
                 "required_args": [],
                 "optional_args": [],
@@ -59,3 +84,23 @@ echo something
         ))
         .success();
 }
+
+#[cfg(not(windows))]
+#[test]
+fn includes_commands_for_another_os() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## clean
+
+~~~powershell
+Remove-Item dist
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .arg("--introspect")
+        .assert()
+        .stdout(contains(r#""name": "clean""#))
+        .success();
+}
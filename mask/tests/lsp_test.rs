@@ -0,0 +1,235 @@
+use assert_cmd::{crate_name, prelude::*};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const URI: &str = "file:///project/maskfile.md";
+
+/// Runs a language server session on the maskfile with the given messages and returns everything
+/// it sent back
+fn run_session(maskfile: &str, messages: Vec<Value>) -> Vec<Value> {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let mut lsp = Command::cargo_bin(crate_name!())
+        .expect("Was not able to find binary")
+        .current_dir(temp.path())
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = lsp.stdin.take().unwrap();
+    let mut all_messages = vec![
+        json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {"capabilities": {}}}),
+        json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": {"uri": URI, "languageId": "markdown", "version": 1, "text": maskfile}
+        }}),
+    ];
+    all_messages.extend(messages);
+    all_messages.push(json!({"jsonrpc": "2.0", "id": 99, "method": "shutdown"}));
+    all_messages.push(json!({"jsonrpc": "2.0", "method": "exit"}));
+    for msg in all_messages {
+        let body = msg.to_string();
+        write!(stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    }
+    drop(stdin);
+
+    let output = lsp.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let mut stdout = String::from_utf8(output.stdout).unwrap();
+    let mut responses = vec![];
+    while let Some(header_end) = stdout.find("\r\n\r\n") {
+        let len: usize = stdout["Content-Length: ".len()..header_end]
+            .parse()
+            .unwrap();
+        let body_start = header_end + 4;
+        responses.push(serde_json::from_str(&stdout[body_start..body_start + len]).unwrap());
+        stdout = stdout[body_start + len..].to_string();
+    }
+    responses
+}
+
+fn request(id: u64, method: &str, line: u32, character: u32) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "method": method, "params": {
+        "textDocument": {"uri": URI},
+        "position": {"line": line, "character": character}
+    }})
+}
+
+fn response(responses: &[Value], id: u64) -> &Value {
+    &responses
+        .iter()
+        .find(|r| r["id"] == id)
+        .expect("missing response")["result"]
+}
+
+#[test]
+fn publishes_diagnostics_when_a_document_is_opened() {
+    let responses = run_session(
+        r#"# Tasks
+
+## build
+
+**OPTIONS**
+* release
+    * flags: --release
+    * typo: oops
+
+~~~bash
+echo "building"
+~~~
+
+## build
+
+~~~bash
+echo "building again"
+~~~
+"#,
+        vec![],
+    );
+
+    let diagnostics = responses
+        .iter()
+        .find(|r| r["method"] == "textDocument/publishDiagnostics")
+        .expect("no diagnostics were published");
+
+    assert_eq!(URI, diagnostics["params"]["uri"]);
+    let diagnostics = diagnostics["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(2, diagnostics.len());
+
+    // Unknown option config key
+    assert_eq!(2, diagnostics[0]["severity"]);
+    assert_eq!(7, diagnostics[0]["range"]["start"]["line"]);
+    // Duplicate command
+    assert_eq!(1, diagnostics[1]["severity"]);
+    assert_eq!(13, diagnostics[1]["range"]["start"]["line"]);
+}
+
+#[test]
+fn provides_the_command_tree_as_document_symbols() {
+    let responses = run_session(
+        r#"# Tasks
+
+## build
+> Builds the project
+
+~~~bash
+echo "building"
+~~~
+
+## services
+
+### services start (name)
+
+~~~bash
+echo "starting $name"
+~~~
+"#,
+        vec![json!({
+            "jsonrpc": "2.0", "id": 1, "method": "textDocument/documentSymbol",
+            "params": {"textDocument": {"uri": URI}}
+        })],
+    );
+
+    let symbols = response(&responses, 1).as_array().unwrap();
+    let names: Vec<&str> = symbols
+        .iter()
+        .map(|s| s["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(vec!["build", "services"], names);
+
+    assert_eq!("Builds the project", symbols[0]["detail"]);
+    assert_eq!(2, symbols[0]["range"]["start"]["line"]);
+    assert_eq!(7, symbols[0]["range"]["end"]["line"]);
+
+    let children = symbols[1]["children"].as_array().unwrap();
+    assert_eq!("start", children[0]["name"]);
+    assert_eq!(11, children[0]["selectionRange"]["start"]["line"]);
+}
+
+#[test]
+fn shows_the_help_output_when_hovering_a_command() {
+    let responses = run_session(
+        r#"# Tasks
+
+## services
+
+### services start (name)
+
+~~~bash
+echo "starting $name"
+~~~
+"#,
+        vec![request(1, "textDocument/hover", 4, 6)],
+    );
+
+    let hover = response(&responses, 1)["contents"]["value"]
+        .as_str()
+        .unwrap();
+    assert!(hover.starts_with("```text\n"));
+    assert!(hover.contains("mask services start [FLAGS] [OPTIONS] <name>"));
+}
+
+#[test]
+fn completes_option_config_keys_and_types() {
+    let responses = run_session(
+        r#"# Tasks
+
+## build
+
+**OPTIONS**
+* release
+    * flags: --release
+    * typo: oops
+
+~~~bash
+echo "building"
+~~~
+"#,
+        vec![
+            request(1, "textDocument/completion", 7, 6),
+            request(2, "textDocument/completion", 2, 3),
+        ],
+    );
+
+    let labels: Vec<&str> = response(&responses, 1)
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c["label"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        vec!["desc", "type", "flags", "choices", "required", "secret"],
+        labels
+    );
+
+    // Nothing to complete outside of an OPTIONS list
+    assert_eq!(&Value::Null, response(&responses, 2));
+}
+
+#[test]
+fn goes_to_the_definition_of_a_mask_command_reference() {
+    let responses = run_session(
+        r#"# Tasks
+
+## build
+
+~~~bash
+echo "building"
+~~~
+
+## release
+
+~~~bash
+$MASK build --release
+~~~
+"#,
+        vec![request(1, "textDocument/definition", 11, 8)],
+    );
+
+    let location = response(&responses, 1);
+    assert_eq!(URI, location["uri"]);
+    assert_eq!(2, location["range"]["start"]["line"]);
+}
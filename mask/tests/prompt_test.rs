@@ -0,0 +1,512 @@
+#![cfg(unix)]
+
+use assert_cmd::cargo::cargo_bin;
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use portable_pty::{native_pty_system, Child, CommandBuilder, PtySize};
+use predicates::str::contains;
+use std::io::{Read, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// A mask process running in a pseudo-terminal so it's interactive
+struct Terminal {
+    child: Box<dyn Child + Send + Sync>,
+    output: Receiver<String>,
+    seen: String,
+    writer: Box<dyn Write + Send>,
+}
+
+impl Terminal {
+    fn spawn(maskfile: &str, args: &[&str]) -> (assert_fs::TempDir, Terminal) {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("maskfile.md").write_str(maskfile).unwrap();
+
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: 24,
+                cols: 80,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .unwrap();
+        let mut cmd = CommandBuilder::new(cargo_bin("mask"));
+        cmd.args(args);
+        cmd.cwd(temp.path());
+        // Plain output is easier to match
+        cmd.env("CLICOLOR", "0");
+        cmd.env_remove("CLICOLOR_FORCE");
+        cmd.env_remove("MASK_ASSUME_YES");
+        let child = pair.slave.spawn_command(cmd).unwrap();
+        drop(pair.slave);
+
+        // Reading blocks until there's output, so do it on another thread
+        let mut reader = pair.master.try_clone_reader().unwrap();
+        let (sender, output) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = [0; 1024];
+            while let Ok(n) = reader.read(&mut buf) {
+                if n == 0
+                    || sender
+                        .send(String::from_utf8_lossy(&buf[..n]).to_string())
+                        .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        let writer = pair.master.take_writer().unwrap();
+        // The master has to stay open for as long as the test runs
+        std::mem::forget(pair.master);
+
+        let terminal = Terminal {
+            child,
+            output,
+            seen: String::new(),
+            writer,
+        };
+        (temp, terminal)
+    }
+
+    /// Waits for the text to be printed and returns everything printed up to and including it
+    fn expect(&mut self, text: &str) -> String {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !self.seen.contains(text) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.output.recv_timeout(remaining) {
+                Ok(chunk) => self.seen += &chunk,
+                Err(_) => panic!("never saw {:?} in output:\n{}", text, self.seen),
+            }
+        }
+        let end = self.seen.find(text).unwrap() + text.len();
+        let printed = self.seen[..end].to_string();
+        self.seen = self.seen[end..].to_string();
+        printed
+    }
+
+    fn send(&mut self, keys: &str) {
+        self.writer.write_all(keys.as_bytes()).unwrap();
+        self.writer.flush().unwrap();
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        // Prompts wait forever for input, so don't leave them running
+        let _ = self.child.kill();
+    }
+}
+
+mod picker {
+    use super::*;
+
+    #[test]
+    fn lists_the_commands_with_their_descriptions() {
+        let (_temp, mut terminal) = Terminal::spawn(
+            r#"
+## build
+
+> Build the project
+
+~~~sh
+echo "Building"
+~~~
+
+## serve (port)
+
+> Serve the app
+
+**OPTIONS**
+* env
+    * flags: --env
+    * type: string
+    * choices: dev, prod
+    * required
+
+~~~sh
+echo "Serving $env on $port"
+~~~
+"#,
+            &[],
+        );
+
+        terminal.expect("Which command do you want to run?");
+        terminal.expect("build  Build the project");
+        terminal.expect("serve  Serve the app");
+    }
+
+    #[test]
+    fn runs_the_picked_command() {
+        let (_temp, mut terminal) = Terminal::spawn(
+            r#"
+## build
+
+> Build the project
+
+~~~sh
+echo "Building"
+~~~
+
+## test
+
+~~~sh
+echo "Testing"
+~~~
+"#,
+            &[],
+        );
+
+        terminal.expect("Which command do you want to run?");
+        terminal.send("build\r");
+        terminal.expect("Building");
+    }
+
+    #[test]
+    fn prompts_for_required_args_and_flags() {
+        let (_temp, mut terminal) = Terminal::spawn(
+            r#"
+## build
+
+> Build the project
+
+~~~sh
+echo "Building"
+~~~
+
+## serve (port)
+
+> Serve the app
+
+**OPTIONS**
+* env
+    * flags: --env
+    * type: string
+    * choices: dev, prod
+    * required
+
+~~~sh
+echo "Serving $env on $port"
+~~~
+"#,
+            &[],
+        );
+
+        terminal.expect("Which command do you want to run?");
+        terminal.send("serve\r");
+        terminal.expect("port");
+        terminal.send("8080\r");
+        terminal.expect("env");
+        // Move down to the second choice
+        terminal.send("\x1b[B\r");
+        terminal.expect("Serving prod on 8080");
+    }
+}
+
+mod missing_values {
+    use super::*;
+
+    #[test]
+    fn prompts_when_opted_in_with_the_prompt_flag() {
+        let (_temp, mut terminal) = Terminal::spawn(
+            r#"
+## serve (port)
+
+> Serve the app
+
+**OPTIONS**
+* env
+    * flags: --env
+    * type: string
+    * choices: dev, prod
+    * required
+
+~~~sh
+echo "Serving $env on $port"
+~~~
+"#,
+            &["--prompt", "serve", "8080"],
+        );
+
+        terminal.expect("env");
+        terminal.send("\r");
+        terminal.expect("Serving dev on 8080");
+    }
+
+    #[test]
+    fn prompts_when_opted_in_with_the_prompt_setting() {
+        let (_temp, mut terminal) = Terminal::spawn(
+            r#"
+## login (user)
+
+**SETTINGS**
+* prompt
+
+**OPTIONS**
+* token
+    * flags: --token
+    * type: string
+    * required
+    * secret
+
+~~~sh
+echo "Logging in $user with a ${#token} char token"
+~~~
+"#,
+            &["login"],
+        );
+
+        terminal.expect("user");
+        terminal.send("ann\r");
+        terminal.expect("token");
+        // Echo is turned off right after the prompt is printed and typing earlier would be lost
+        std::thread::sleep(Duration::from_millis(300));
+        terminal.send("hunter2\r");
+        let printed = terminal.expect("Logging in ann with a 7 char token");
+
+        assert!(!printed.contains("hunter2"), "the secret was shown");
+    }
+
+    #[test]
+    fn does_not_prompt_for_a_commands_own_prompt_flag() {
+        let (_temp, mut terminal) = Terminal::spawn(
+            r#"
+## serve
+
+**OPTIONS**
+* prompt
+    * flags: --prompt
+* env
+    * flags: --env
+    * type: string
+    * required
+
+~~~sh
+echo "Serving $env"
+~~~
+"#,
+            &["serve", "--prompt"],
+        );
+
+        terminal.expect("--env <env>");
+    }
+
+    #[test]
+    fn fails_as_usual_without_a_terminal() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("maskfile.md")
+            .write_str(
+                r#"
+## serve (port)
+
+> Serve the app
+
+**OPTIONS**
+* env
+    * flags: --env
+    * type: string
+    * choices: dev, prod
+    * required
+
+~~~sh
+echo "Serving $env on $port"
+~~~
+"#,
+            )
+            .unwrap();
+
+        std::process::Command::cargo_bin("mask")
+            .unwrap()
+            .current_dir(temp.path())
+            .args(["--prompt", "serve", "8080"])
+            .assert()
+            .stderr(contains("--env <env>"))
+            .failure();
+    }
+}
+
+mod confirm {
+    use super::*;
+
+    fn run_mask(temp: &assert_fs::TempDir) -> std::process::Command {
+        let mut mask = std::process::Command::cargo_bin("mask").unwrap();
+        mask.current_dir(temp.path()).env_remove("MASK_ASSUME_YES");
+        mask
+    }
+
+    #[test]
+    fn runs_the_command_when_confirmed() {
+        let (_temp, mut terminal) = Terminal::spawn(
+            r#"
+## reset
+
+**SETTINGS**
+* confirm: Drop the database?
+
+~~~sh
+echo "Dropping the database"
+~~~
+"#,
+            &["reset"],
+        );
+
+        terminal.expect("Drop the database?");
+        terminal.send("y");
+        terminal.expect("Dropping the database");
+    }
+
+    #[test]
+    fn does_not_run_the_command_when_refused() {
+        let (_temp, mut terminal) = Terminal::spawn(
+            r#"
+## reset
+
+**SETTINGS**
+* confirm: Drop the database?
+
+~~~sh
+echo "Dropping the database"
+~~~
+"#,
+            &["reset"],
+        );
+
+        terminal.expect("Drop the database?");
+        terminal.send("n");
+        let printed = terminal.expect("`reset` was not confirmed");
+
+        assert!(!printed.contains("Dropping the database"));
+    }
+
+    #[test]
+    fn refuses_without_a_terminal() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("maskfile.md")
+            .write_str(
+                r#"
+## reset
+
+**SETTINGS**
+* confirm: Drop the database?
+
+~~~sh
+echo "Dropping the database"
+~~~
+"#,
+            )
+            .unwrap();
+
+        run_mask(&temp)
+            .arg("reset")
+            .assert()
+            .stdout("")
+            .stderr(contains("`reset` was not confirmed"))
+            .failure();
+    }
+
+    #[test]
+    fn skips_the_confirmation_with_the_yes_flag() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("maskfile.md")
+            .write_str(
+                r#"
+## reset
+
+**SETTINGS**
+* confirm: Drop the database?
+
+~~~sh
+echo "Dropping the database"
+~~~
+"#,
+            )
+            .unwrap();
+
+        run_mask(&temp)
+            .args(["--yes", "reset"])
+            .assert()
+            .stdout(contains("Dropping the database"))
+            .success();
+    }
+
+    #[test]
+    fn skips_the_confirmation_with_the_yes_flag_after_the_command() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("maskfile.md")
+            .write_str(
+                r#"
+## reset
+
+**SETTINGS**
+* confirm: Drop the database?
+
+~~~sh
+echo "Dropping the database"
+~~~
+"#,
+            )
+            .unwrap();
+
+        run_mask(&temp)
+            .args(["reset", "--yes"])
+            .assert()
+            .stdout(contains("Dropping the database"))
+            .success();
+    }
+
+    #[test]
+    fn does_not_skip_the_confirmation_with_a_commands_own_yes_flag() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("maskfile.md")
+            .write_str(
+                r#"
+## reset
+
+**SETTINGS**
+* confirm: Drop the database?
+
+**OPTIONS**
+* yes
+    * flags: --yes
+
+~~~sh
+echo "Dropping the database"
+~~~
+"#,
+            )
+            .unwrap();
+
+        run_mask(&temp)
+            .args(["reset", "--yes"])
+            .assert()
+            .stdout("")
+            .stderr(contains("`reset` was not confirmed"))
+            .failure();
+    }
+
+    #[test]
+    fn skips_the_confirmation_with_the_env_var() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("maskfile.md")
+            .write_str(
+                r#"
+## reset
+
+**SETTINGS**
+* confirm: Drop the database?
+
+~~~sh
+echo "Dropping the database"
+~~~
+"#,
+            )
+            .unwrap();
+
+        run_mask(&temp)
+            .arg("reset")
+            .env("MASK_ASSUME_YES", "1")
+            .assert()
+            .stdout(contains("Dropping the database"))
+            .success();
+    }
+}
@@ -0,0 +1,170 @@
+#![cfg(unix)]
+
+mod common;
+use assert_cmd::prelude::*;
+use common::MaskCommandExt;
+use predicates::str::contains;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// PATH with a fake-node program that prints the given version in the maskfile's dir
+fn path_with_fake_node(maskfile_path: &Path, version: &str) -> String {
+    let dir = maskfile_path.parent().unwrap();
+    let program = dir.join("fake-node");
+    std::fs::write(&program, format!("#!/bin/sh\necho v{}\n", version)).unwrap();
+    std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+    format!("{}:{}", dir.display(), std::env::var("PATH").unwrap())
+}
+
+#[test]
+fn exits_with_error_when_a_required_program_is_not_in_path() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## missing
+
+**SETTINGS**
+* requires: sh, definitely-not-installed
+
+~~~sh
+echo "Running"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .command("missing")
+        .assert()
+        .stdout("")
+        .stderr(contains(
+            "`missing` needs `definitely-not-installed` (not found in PATH)",
+        ))
+        .code(1)
+        .failure();
+}
+
+#[test]
+fn runs_when_the_required_version_matches() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## new_node
+
+**SETTINGS**
+* requires: fake-node>=18
+
+~~~sh
+echo "Running"
+~~~
+"#,
+    );
+    let path = path_with_fake_node(&maskfile_path, "18.17.0");
+
+    common::run_mask(&maskfile_path)
+        .env("PATH", path)
+        .command("new_node")
+        .assert()
+        .stdout("Running\n")
+        .success();
+}
+
+#[test]
+fn exits_with_error_when_the_required_version_does_not_match() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## old_node
+
+**SETTINGS**
+* requires: fake-node<18
+
+~~~sh
+echo "Running"
+~~~
+"#,
+    );
+    let path = path_with_fake_node(&maskfile_path, "18.17.0");
+
+    common::run_mask(&maskfile_path)
+        .env("PATH", path)
+        .command("old_node")
+        .assert()
+        .stdout("")
+        .stderr(contains("`old_node` needs `fake-node<18` (found 18.17.0)"))
+        .code(1)
+        .failure();
+}
+
+#[test]
+fn runs_when_the_preconditions_pass() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## deploy (env)
+
+**SETTINGS**
+* preconditions:
+    * `test -f "$MASKFILE_DIR/maskfile.md"`
+    * `test "$env" != production`: Deploy to production from CI instead
+
+~~~sh
+echo "Deploying to $env"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .cli("deploy staging")
+        .assert()
+        .stdout("Deploying to staging\n")
+        .success();
+}
+
+#[test]
+fn exits_with_the_message_of_a_failed_precondition() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## deploy (env)
+
+**SETTINGS**
+* preconditions:
+    * `test -f "$MASKFILE_DIR/maskfile.md"`
+    * `test "$env" != production`: Deploy to production from CI instead
+
+~~~sh
+echo "Deploying to $env"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .cli("deploy production")
+        .assert()
+        .stdout("")
+        .stderr(contains("Deploy to production from CI instead"))
+        .code(1)
+        .failure();
+}
+
+#[test]
+fn names_the_failed_check_without_a_message() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## check
+
+**SETTINGS**
+* preconditions:
+    * `test -f missing.txt`
+
+~~~sh
+echo "Running"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .command("check")
+        .assert()
+        .stdout("")
+        .stderr(contains(
+            "`check` can't run since `test -f missing.txt` failed",
+        ))
+        .code(1)
+        .failure();
+}
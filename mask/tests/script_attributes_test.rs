@@ -0,0 +1,99 @@
+mod common;
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use common::MaskCommandExt;
+use predicates::str::contains;
+
+#[test]
+fn runs_the_script_in_the_cwd_relative_to_the_maskfile() {
+    let (temp, maskfile_path) = common::maskfile(
+        r#"
+## where
+
+~~~bash {cwd="web"}
+basename "$(pwd)"
+~~~
+
+~~~powershell {cwd="web"}
+Split-Path -Leaf (Get-Location)
+~~~
+"#,
+    );
+    temp.child("web").create_dir_all().unwrap();
+
+    common::run_mask(&maskfile_path)
+        .current_dir("tests")
+        .command("where")
+        .assert()
+        .stdout(contains("web"))
+        .success();
+}
+
+#[test]
+fn sets_the_env_vars() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## env
+
+~~~bash {env="NODE_ENV=production PORT=8080"}
+echo "$NODE_ENV on $PORT"
+~~~
+
+~~~powershell {env="NODE_ENV=production PORT=8080"}
+Write-Output "$env:NODE_ENV on $env:PORT"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .command("env")
+        .assert()
+        .stdout(contains("production on 8080"))
+        .success();
+}
+
+#[test]
+fn sets_env_vars_separated_by_commas() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## env
+
+~~~bash {env="NODE_ENV=production, PORT=8080"}
+echo "$NODE_ENV on $PORT"
+~~~
+
+~~~powershell {env="NODE_ENV=production, PORT=8080"}
+Write-Output "$env:NODE_ENV on $env:PORT"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .command("env")
+        .assert()
+        .stdout(contains("production on 8080"))
+        .success();
+}
+
+#[test]
+fn args_take_precedence_over_env_vars() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## serve (port)
+
+~~~bash {env="port=3000"}
+echo "Serving on $port"
+~~~
+
+~~~powershell {env="port=3000"}
+Write-Output "Serving on $env:port"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .cli("serve 8080")
+        .assert()
+        .stdout(contains("Serving on 8080"))
+        .success();
+}
@@ -0,0 +1,102 @@
+#![cfg(unix)]
+
+mod common;
+use assert_cmd::prelude::*;
+use common::MaskCommandExt;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdout, Stdio};
+
+/// Starts mask and waits for the script to be ready for signals
+fn spawn_ready(maskfile_path: &PathBuf, command: &'static str) -> (Child, BufReader<ChildStdout>) {
+    let mut mask = common::run_mask(maskfile_path)
+        .command(command)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdout = BufReader::new(mask.stdout.take().unwrap());
+    assert_eq!("Ready\n", next_line(&mut stdout));
+
+    (mask, stdout)
+}
+
+fn next_line(stdout: &mut BufReader<ChildStdout>) -> String {
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    line
+}
+
+fn send_signal(child: &Child, signal: &str) {
+    std::process::Command::new("kill")
+        .arg(format!("-{}", signal))
+        .arg(child.id().to_string())
+        .status()
+        .unwrap();
+}
+
+#[test]
+fn exits_with_128_plus_the_signal_when_the_script_is_killed() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## crash
+
+~~~sh
+kill -KILL $$
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .command("crash")
+        .assert()
+        .code(128 + 9)
+        .failure();
+}
+
+#[test]
+fn forwards_sigterm_to_the_script() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## server
+
+~~~sh
+trap 'echo "Got TERM"; exit 3' TERM
+echo "Ready"
+while true; do sleep 0.1; done
+~~~
+"#,
+    );
+
+    let (mut mask, mut stdout) = spawn_ready(&maskfile_path, "server");
+
+    send_signal(&mask, "TERM");
+
+    assert_eq!("Got TERM\n", next_line(&mut stdout));
+    assert_eq!(Some(3), mask.wait().unwrap().code());
+}
+
+#[test]
+fn forwards_sigint_to_scripts_in_their_own_process_group() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## grouped
+
+**SETTINGS**
+* timeout: 1m
+
+~~~sh
+trap 'echo "Got INT"; exit 4' INT
+echo "Ready"
+while true; do sleep 0.1; done
+~~~
+"#,
+    );
+
+    let (mut mask, mut stdout) = spawn_ready(&maskfile_path, "grouped");
+
+    send_signal(&mask, "INT");
+
+    assert_eq!("Got INT\n", next_line(&mut stdout));
+    assert_eq!(Some(4), mask.wait().unwrap().code());
+}
@@ -134,3 +134,363 @@ Write-Output "Start all services"
             .success();
     }
 }
+
+mod aliases {
+    use super::*;
+
+    const MASKFILE: &str = r#"
+## test (file)
+
+**SETTINGS**
+* aliases: t, tst
+
+~~~bash
+echo "Testing $file"
+~~~
+
+~~~powershell
+Write-Output "Testing $env:file"
+~~~
+"#;
+
+    #[test]
+    fn runs_the_command_with_an_alias() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .cli("tst app.rs")
+            .assert()
+            .stdout(contains("Testing app.rs"))
+            .success();
+    }
+
+    #[test]
+    fn shows_aliases_in_the_help_output() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .command("--help")
+            .assert()
+            .stdout(contains("[aliases: t, tst]"))
+            .success();
+    }
+
+    #[test]
+    fn runs_the_sibling_when_an_alias_is_its_name() {
+        let (_temp, maskfile_path) = common::maskfile(
+            r#"
+## test
+
+**SETTINGS**
+* aliases: t, build
+
+~~~bash
+echo "Testing"
+~~~
+
+## build
+
+~~~bash
+echo "Building"
+~~~
+"#,
+        );
+
+        common::run_mask(&maskfile_path)
+            .command("build")
+            .assert()
+            .stdout(contains("Building"))
+            .stderr("")
+            .success();
+    }
+
+    #[test]
+    fn points_out_a_taken_alias_when_running_its_command() {
+        let (_temp, maskfile_path) = common::maskfile(
+            r#"
+## test
+
+**SETTINGS**
+* aliases: t, build
+
+~~~bash
+echo "Testing"
+~~~
+
+## build
+
+~~~bash
+echo "Building"
+~~~
+"#,
+        );
+
+        common::run_mask(&maskfile_path)
+            .command("t")
+            .assert()
+            .stdout(contains("Testing"))
+            .stderr(contains(format!(
+                "{} {}:2: alias `build` of command `test` is already the name of a command",
+                "ERROR:".red(),
+                maskfile_path.display()
+            )))
+            .success();
+    }
+}
+
+mod hidden {
+    use super::*;
+    use predicates::boolean::PredicateBooleanExt;
+
+    const MASKFILE: &str = r#"
+## build
+
+~~~bash
+$MASK helper
+~~~
+
+~~~powershell
+Write-Output "Running the helper"
+~~~
+
+## helper
+
+**SETTINGS**
+* hidden
+
+~~~bash
+echo "Running the helper"
+~~~
+
+~~~powershell
+Write-Output "Running the helper"
+~~~
+"#;
+
+    #[test]
+    fn hides_the_command_from_the_help_output() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .command("--help")
+            .assert()
+            .stdout(contains("build"))
+            .stdout(contains("helper").not())
+            .success();
+    }
+
+    #[test]
+    fn can_still_be_run() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .command("helper")
+            .assert()
+            .stdout(contains("Running the helper"))
+            .success();
+    }
+}
+
+mod default {
+    use super::*;
+
+    const MASKFILE: &str = r#"
+## dev
+
+**SETTINGS**
+* default
+
+~~~bash
+echo "Starting the dev server"
+~~~
+
+~~~powershell
+Write-Output "Starting the dev server"
+~~~
+
+## db
+
+### db migrate
+
+~~~bash
+echo "Migrating"
+~~~
+
+~~~powershell
+Write-Output "Migrating"
+~~~
+
+### db status
+
+**SETTINGS**
+* default
+
+~~~bash
+echo "Up to date"
+~~~
+
+~~~powershell
+Write-Output "Up to date"
+~~~
+"#;
+
+    #[test]
+    fn runs_the_default_command_when_none_is_given() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .assert()
+            .stdout(contains("Starting the dev server"))
+            .success();
+    }
+
+    #[test]
+    fn runs_the_default_subcommand_of_a_parent_without_a_script() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .command("db")
+            .assert()
+            .stdout(contains("Up to date"))
+            .success();
+    }
+
+    #[test]
+    fn still_runs_other_commands() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .cli("db migrate")
+            .assert()
+            .stdout(contains("Migrating"))
+            .success();
+    }
+}
+
+#[cfg(not(windows))]
+mod setup {
+    use super::*;
+
+    const MASKFILE: &str = r#"
+## docker
+
+**SETTINGS**
+* setup
+
+~~~sh
+echo "Logging in"
+export DOCKER_TOKEN="secret"
+~~~
+
+~~~sh finally
+echo "Logging out after $MASK_EXIT_CODE"
+~~~
+
+### docker push
+
+~~~sh
+echo "Pushing with $DOCKER_TOKEN"
+~~~
+
+### docker pull (image)
+
+~~~sh
+echo "Pulling $image"
+exit 2
+~~~
+
+## broken
+
+**SETTINGS**
+* setup
+
+~~~sh
+exit 3
+~~~
+
+### broken child
+
+~~~sh
+echo "Never runs"
+~~~
+"#;
+
+    #[test]
+    fn runs_before_and_after_subcommands_and_passes_on_exports() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .cli("docker push")
+            .assert()
+            .stdout("Logging in\nPushing with secret\nLogging out after 0\n")
+            .success();
+    }
+
+    #[test]
+    fn runs_after_subcommands_that_fail() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .cli("docker pull alpine")
+            .assert()
+            .stdout("Logging in\nPulling alpine\nLogging out after 2\n")
+            .code(2)
+            .failure();
+    }
+
+    #[test]
+    fn stops_when_the_setup_fails() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .cli("broken child")
+            .assert()
+            .stdout("")
+            .code(3)
+            .failure();
+    }
+
+    #[test]
+    fn warns_when_the_setup_replaces_the_exit_trap() {
+        let (_temp, maskfile_path) = common::maskfile(
+            r#"
+## docker
+
+**SETTINGS**
+* setup
+
+~~~sh
+trap 'echo "Cleaning up"' EXIT
+export DOCKER_TOKEN="secret"
+~~~
+
+### docker push
+
+~~~sh
+echo "Pushing with $DOCKER_TOKEN"
+~~~
+"#,
+        );
+
+        common::run_mask(&maskfile_path)
+            .cli("docker push")
+            .assert()
+            .stdout("Cleaning up\nPushing with \n")
+            .stderr(contains(
+                "`docker` set its own EXIT trap, so the variables it exports aren't passed on",
+            ))
+            .success();
+    }
+
+    #[test]
+    fn requires_a_subcommand() {
+        let (_temp, maskfile_path) = common::maskfile(MASKFILE);
+
+        common::run_mask(&maskfile_path)
+            .command("docker")
+            .assert()
+            .stdout("")
+            .stderr(contains("'mask docker' requires a subcommand"))
+            .failure();
+    }
+}
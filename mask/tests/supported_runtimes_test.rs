@@ -233,3 +233,64 @@ echo "Hello, " . $name . "!\n";
         .stdout(contains("Hello, World!"))
         .success();
 }
+
+#[test]
+fn runs_the_script_for_the_current_os() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## os
+~~~sh os=linux
+echo "Hello from linux!"
+~~~
+
+~~~sh os=macos
+echo "Hello from macos!"
+~~~
+
+~~~powershell
+Write-Output "Hello from windows!"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .command("os")
+        .assert()
+        .stdout(format!("Hello from {}!\n", std::env::consts::OS))
+        .success();
+}
+
+#[cfg(not(windows))]
+#[test]
+fn leaves_out_commands_for_another_os() {
+    use predicates::boolean::PredicateBooleanExt;
+
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## build
+
+~~~sh
+echo "Building"
+~~~
+
+## clean
+
+~~~powershell
+Remove-Item dist
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .command("--help")
+        .assert()
+        .stdout(contains("build"))
+        .stdout(contains("clean").not())
+        .success();
+
+    common::run_mask(&maskfile_path)
+        .command("clean")
+        .assert()
+        .stderr(contains("Found argument 'clean' which wasn't expected"))
+        .failure();
+}
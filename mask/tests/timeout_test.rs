@@ -0,0 +1,214 @@
+#![cfg(unix)]
+
+mod common;
+use assert_cmd::prelude::*;
+use common::MaskCommandExt;
+use predicates::str::contains;
+use std::time::{Duration, Instant};
+
+/// Runs mask and checks that it didn't wait for the sleep to finish
+fn assert_stopped_early(mask: &mut std::process::Command) -> assert_cmd::assert::Assert {
+    let start = Instant::now();
+    let assert = mask.assert();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert
+}
+
+#[test]
+fn stops_commands_that_run_longer_than_their_timeout() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## slow
+
+**SETTINGS**
+* timeout: 1s
+
+~~~sh
+echo "Starting"
+sleep 10
+echo "Finished"
+~~~
+"#,
+    );
+
+    assert_stopped_early(common::run_mask(&maskfile_path).command("slow"))
+        .stdout("Starting\n")
+        .stderr(contains("`slow` timed out after 1s"))
+        .code(124)
+        .failure();
+}
+
+#[test]
+fn stops_commands_that_run_longer_than_the_timeout_flag() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## sleep (seconds)
+
+~~~sh
+sleep $seconds
+echo "Slept for $seconds"
+~~~
+"#,
+    );
+
+    assert_stopped_early(common::run_mask(&maskfile_path).cli("--timeout 1s sleep 10"))
+        .stderr(contains("`sleep` timed out after 1s"))
+        .code(124)
+        .failure();
+}
+
+#[test]
+fn stops_commands_that_run_longer_than_the_timeout_flag_after_the_command() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## sleep (seconds)
+
+~~~sh
+sleep $seconds
+~~~
+"#,
+    );
+
+    assert_stopped_early(common::run_mask(&maskfile_path).cli("sleep 10 --timeout 1s"))
+        .stderr(contains("`sleep` timed out after 1s"))
+        .code(124)
+        .failure();
+}
+
+#[test]
+fn passes_a_commands_own_timeout_flag_to_it() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## wait
+
+**OPTIONS**
+* timeout
+    * flags: --timeout
+    * type: string
+
+~~~sh
+echo "Waiting up to $timeout"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .cli("wait --timeout 0s")
+        .assert()
+        .stdout("Waiting up to 0s\n")
+        .success();
+}
+
+#[test]
+fn stops_code_blocks_that_run_longer_than_their_timeout_attribute() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## slow_block
+
+~~~sh
+echo "Quick block"
+~~~
+
+~~~sh {timeout="1s"}
+sleep 10
+~~~
+"#,
+    );
+
+    assert_stopped_early(common::run_mask(&maskfile_path).command("slow_block"))
+        .stdout("Quick block\n")
+        .stderr(contains("`slow_block` timed out after 1s"))
+        .code(124)
+        .failure();
+}
+
+#[test]
+fn lets_the_script_clean_up_after_sigterm() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## graceful
+
+**SETTINGS**
+* timeout: 1s
+
+~~~bash
+trap 'echo "Cleaning up"; exit 0' TERM
+sleep 10 &
+wait
+~~~
+"#,
+    );
+
+    assert_stopped_early(common::run_mask(&maskfile_path).command("graceful"))
+        .stdout("Cleaning up\n")
+        .code(124)
+        .failure();
+}
+
+#[test]
+fn runs_finally_blocks_after_a_timeout() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## slow_with_cleanup
+
+**SETTINGS**
+* timeout: 1s
+
+~~~sh
+sleep 10
+~~~
+
+~~~sh finally
+echo "Cleaning up after $MASK_EXIT_CODE"
+~~~
+"#,
+    );
+
+    assert_stopped_early(common::run_mask(&maskfile_path).command("slow_with_cleanup"))
+        .stdout("Cleaning up after 124\n")
+        .code(124)
+        .failure();
+}
+
+#[test]
+fn runs_commands_that_finish_in_time() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## sleep (seconds)
+
+~~~sh
+sleep $seconds
+echo "Slept for $seconds"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .cli("--timeout 10s sleep 0")
+        .assert()
+        .stdout("Slept for 0\n")
+        .success();
+}
+
+#[test]
+fn exits_with_error_for_an_invalid_timeout_flag() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## sleep (seconds)
+
+~~~sh
+sleep $seconds
+echo "Slept for $seconds"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .cli("--timeout soon sleep 0")
+        .assert()
+        .stderr(contains(
+            "timeout `soon` is not a duration like 10m or 1h 30m",
+        ))
+        .code(1)
+        .failure();
+}
@@ -0,0 +1,299 @@
+#![cfg(unix)]
+
+mod common;
+use assert_cmd::prelude::*;
+use common::MaskCommandExt;
+use predicates::str::contains;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+fn write_source(maskfile_path: &Path, contents: &str) {
+    let src = maskfile_path.parent().unwrap().join("src");
+    fs::create_dir_all(&src).unwrap();
+    fs::write(src.join("main.txt"), contents).unwrap();
+}
+
+/// Makes the file look like it changed after everything else
+fn touch_later(path: &Path) {
+    let later = SystemTime::now() + Duration::from_secs(60);
+    let file = fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(later).unwrap();
+}
+
+#[test]
+fn skips_commands_whose_outputs_are_newer_than_their_sources() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## build
+
+**SETTINGS**
+* sources: src/*.txt
+* outputs: out.txt
+
+~~~sh
+cat "$MASKFILE_DIR"/src/*.txt > "$MASKFILE_DIR/out.txt"
+echo "Building"
+~~~
+"#,
+    );
+    write_source(&maskfile_path, "a");
+
+    common::run_mask(&maskfile_path)
+        .command("build")
+        .assert()
+        .stdout("Building\n")
+        .success();
+
+    common::run_mask(&maskfile_path)
+        .command("build")
+        .assert()
+        .stdout("`build` is up to date\n")
+        .success();
+
+    touch_later(&maskfile_path.parent().unwrap().join("src/main.txt"));
+
+    common::run_mask(&maskfile_path)
+        .command("build")
+        .assert()
+        .stdout("Building\n")
+        .success();
+}
+
+#[test]
+fn skips_commands_whose_sources_have_the_same_fingerprint() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## lint
+
+**SETTINGS**
+* sources: src/*.txt
+
+~~~sh
+echo "Linting"
+~~~
+"#,
+    );
+    write_source(&maskfile_path, "a");
+
+    common::run_mask(&maskfile_path)
+        .command("lint")
+        .assert()
+        .stdout("Linting\n")
+        .success();
+
+    assert!(maskfile_path
+        .parent()
+        .unwrap()
+        .join(".mask/fingerprints/lint")
+        .is_file());
+
+    common::run_mask(&maskfile_path)
+        .command("lint")
+        .assert()
+        .stdout("`lint` is up to date\n")
+        .success();
+
+    write_source(&maskfile_path, "b");
+
+    common::run_mask(&maskfile_path)
+        .command("lint")
+        .assert()
+        .stdout("Linting\n")
+        .success();
+}
+
+#[test]
+fn includes_the_files_within_directories_matched_by_sources() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## lint
+
+**SETTINGS**
+* sources: src/**
+
+~~~sh
+echo "Linting"
+~~~
+"#,
+    );
+    write_source(&maskfile_path, "a");
+
+    for (contents, output) in [
+        ("a", "Linting\n"),
+        ("a", "`lint` is up to date\n"),
+        ("b", "Linting\n"),
+    ] {
+        write_source(&maskfile_path, contents);
+        common::run_mask(&maskfile_path)
+            .command("lint")
+            .assert()
+            .stdout(output)
+            .success();
+    }
+}
+
+#[test]
+fn warns_when_sources_match_no_files() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## lint
+
+**SETTINGS**
+* sources: src/*.txt
+
+~~~sh
+echo "Linting"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .command("lint")
+        .assert()
+        .stdout("Linting\n")
+        .stderr(contains(
+            "`lint` has no files matching its `sources`, so it always runs",
+        ))
+        .success();
+}
+
+#[test]
+fn runs_up_to_date_commands_with_force() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## lint
+
+**SETTINGS**
+* sources: src/*.txt
+
+~~~sh
+echo "Linting"
+~~~
+"#,
+    );
+    write_source(&maskfile_path, "a");
+
+    common::run_mask(&maskfile_path)
+        .command("lint")
+        .assert()
+        .success();
+
+    common::run_mask(&maskfile_path)
+        .cli("--force lint")
+        .assert()
+        .stdout("Linting\n")
+        .success();
+}
+
+#[test]
+fn runs_commands_again_after_they_failed() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## fail
+
+**SETTINGS**
+* sources: src/*.txt
+
+~~~sh
+echo "Failing"
+exit 1
+~~~
+"#,
+    );
+    write_source(&maskfile_path, "a");
+
+    for _ in 0..2 {
+        common::run_mask(&maskfile_path)
+            .command("fail")
+            .assert()
+            .stdout("Failing\n")
+            .failure();
+    }
+}
+
+#[test]
+fn runs_commands_again_with_different_args_and_flags() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## build
+
+**SETTINGS**
+* sources: src/*.txt
+
+**OPTIONS**
+* release
+    * flags: --release
+
+~~~sh
+echo "Building release=$release"
+~~~
+"#,
+    );
+    write_source(&maskfile_path, "a");
+
+    common::run_mask(&maskfile_path)
+        .command("build")
+        .assert()
+        .stdout("Building release=\n")
+        .success();
+
+    common::run_mask(&maskfile_path)
+        .cli("build --release")
+        .assert()
+        .stdout("Building release=true\n")
+        .success();
+
+    common::run_mask(&maskfile_path)
+        .cli("build --release")
+        .assert()
+        .stdout("`build` is up to date\n")
+        .success();
+}
+
+#[test]
+fn keeps_subcommands_of_the_same_name_apart() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## docker
+
+### docker build
+
+**SETTINGS**
+* sources: src/*.txt
+
+~~~sh
+echo "Building the image"
+~~~
+
+## web
+
+### web build
+
+**SETTINGS**
+* sources: src/*.txt
+
+~~~sh
+echo "Building the site"
+~~~
+"#,
+    );
+    write_source(&maskfile_path, "a");
+
+    for (args, output) in [
+        ("docker build", "Building the image\n"),
+        ("web build", "Building the site\n"),
+        ("docker build", "`build` is up to date\n"),
+        ("web build", "`build` is up to date\n"),
+    ] {
+        common::run_mask(&maskfile_path)
+            .cli(args)
+            .assert()
+            .stdout(output)
+            .success();
+    }
+
+    let fingerprints = maskfile_path.parent().unwrap().join(".mask/fingerprints");
+    assert!(fingerprints.join("docker.build").is_file());
+    assert!(fingerprints.join("web.build").is_file());
+}
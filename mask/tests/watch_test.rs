@@ -0,0 +1,190 @@
+#![cfg(unix)]
+
+mod common;
+use assert_cmd::prelude::*;
+use common::MaskCommandExt;
+use predicates::str::contains;
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+/// A watching mask whose process group is killed when it's dropped, since it never exits
+struct Watching {
+    mask: Child,
+    lines: Receiver<String>,
+    logs: Receiver<String>,
+}
+
+impl Watching {
+    fn start(maskfile_path: &PathBuf, command: &'static str) -> Self {
+        let mut mask = common::run_mask(maskfile_path)
+            .arg("--watch")
+            .command(command)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .unwrap();
+
+        let lines = read_lines(mask.stdout.take().unwrap());
+        let logs = read_lines(mask.stderr.take().unwrap());
+        Watching { mask, lines, logs }
+    }
+
+    /// Changes only count once mask is done with the run before them
+    fn wait_for_idle(&self) {
+        loop {
+            let log = self
+                .logs
+                .recv_timeout(Duration::from_secs(10))
+                .expect("mask didn't start waiting for changes in time");
+            if log.contains("waiting for changes") {
+                return;
+            }
+        }
+    }
+
+    fn next_line(&self) -> String {
+        self.lines
+            .recv_timeout(Duration::from_secs(10))
+            .expect("mask didn't print another line in time")
+    }
+
+    fn assert_no_line_within(&self, timeout: Duration) {
+        if let Ok(line) = self.lines.recv_timeout(timeout) {
+            panic!("mask printed `{}` without any changes", line);
+        }
+    }
+}
+
+impl Drop for Watching {
+    fn drop(&mut self) {
+        std::process::Command::new("kill")
+            .args(["-KILL", "--", &format!("-{}", self.mask.id())])
+            .status()
+            .unwrap();
+        let _ = self.mask.wait();
+    }
+}
+
+fn read_lines(output: impl Read + Send + 'static) -> Receiver<String> {
+    let (tx, lines) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(output).lines() {
+            if tx.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    lines
+}
+
+fn write_source(maskfile_path: &Path, contents: &str) {
+    let src = maskfile_path.parent().unwrap().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("a.txt"), contents).unwrap();
+}
+
+#[test]
+fn runs_the_command_again_when_a_watched_file_changes() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## show
+
+**SETTINGS**
+* watch: src/*.txt
+
+~~~sh
+cat "$MASKFILE_DIR/src/a.txt"
+~~~
+"#,
+    );
+    write_source(&maskfile_path, "one\n");
+
+    let watching = Watching::start(&maskfile_path, "show");
+    assert_eq!("one", watching.next_line());
+    watching.wait_for_idle();
+
+    write_source(&maskfile_path, "two\n");
+    assert_eq!("two", watching.next_line());
+}
+
+#[test]
+fn restarts_the_command_when_it_is_still_running() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## serve
+
+**SETTINGS**
+* watch: src/*.txt
+
+~~~sh
+echo "Serving $(cat "$MASKFILE_DIR/src/a.txt")"
+sleep 30
+~~~
+"#,
+    );
+    write_source(&maskfile_path, "one\n");
+
+    let watching = Watching::start(&maskfile_path, "serve");
+    assert_eq!("Serving one", watching.next_line());
+
+    write_source(&maskfile_path, "two\n");
+    assert_eq!("Serving two", watching.next_line());
+
+    write_source(&maskfile_path, "three\n");
+    assert_eq!("Serving three", watching.next_line());
+}
+
+#[test]
+fn ignores_changes_to_the_outputs_of_the_command() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## build
+
+**SETTINGS**
+* watch: src/*.txt
+* outputs: src/out.txt
+
+~~~sh
+echo "Building $(cat "$MASKFILE_DIR/src/a.txt")"
+date > "$MASKFILE_DIR/src/out.txt"
+~~~
+"#,
+    );
+    write_source(&maskfile_path, "one\n");
+
+    let watching = Watching::start(&maskfile_path, "build");
+    assert_eq!("Building one", watching.next_line());
+    watching.wait_for_idle();
+    watching.assert_no_line_within(Duration::from_secs(1));
+
+    write_source(&maskfile_path, "two\n");
+    assert_eq!("Building two", watching.next_line());
+    watching.assert_no_line_within(Duration::from_secs(1));
+}
+
+#[test]
+fn fails_when_the_command_has_no_files_to_watch() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"
+## build
+
+~~~sh
+echo "Building"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .arg("--watch")
+        .command("build")
+        .assert()
+        .stderr(contains(
+            "`build` has no files to watch. Add the `watch` or `sources` setting.",
+        ))
+        .failure();
+}
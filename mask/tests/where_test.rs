@@ -0,0 +1,73 @@
+mod common;
+use assert_cmd::prelude::*;
+use colored::*;
+use common::MaskCommandExt;
+use predicates::str::contains;
+
+#[test]
+fn prints_the_file_and_line_of_a_command() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"# Tasks
+
+## build
+
+~~~bash
+echo "building"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .cli("where build")
+        .assert()
+        .stdout(contains(format!("{}:3", maskfile_path.display())))
+        .success();
+}
+
+#[test]
+fn prints_the_file_and_line_of_a_subcommand() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"# Tasks
+
+## services
+
+### services start (service_name)
+
+~~~bash
+echo "Starting service $service_name"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .cli("where services start")
+        .assert()
+        .stdout(contains(format!("{}:5", maskfile_path.display())))
+        .success();
+}
+
+#[test]
+fn exits_with_error_when_command_is_not_found() {
+    let (_temp, maskfile_path) = common::maskfile(
+        r#"# Tasks
+
+## services
+
+### services start (service_name)
+
+~~~bash
+echo "Starting service $service_name"
+~~~
+"#,
+    );
+
+    common::run_mask(&maskfile_path)
+        .cli("where services stop")
+        .assert()
+        .code(1)
+        .stderr(contains(format!(
+            "{} command `services stop` not found",
+            "ERROR:".red()
+        )))
+        .failure();
+}